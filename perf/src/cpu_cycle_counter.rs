@@ -2,8 +2,42 @@
 //! This version uses pthread_create interposition for fully automatic
 //! and accurate tracking of the entire lifecycle of every thread, and
 //! it deducts time spent in external library calls.
+//!
+//! `pthread_create` interposition alone misses threads that never go
+//! through `thread_start_wrapper` (the main thread, threads spawned via a
+//! raw `clone()`, threads reached via a different interposition layer), so
+//! a process-wide `pthread_key_t` (`CLEANUP_KEY`) backs up the wrapper with
+//! an authoritative destructor-driven finalizer; see `finalize_slot` and
+//! `cleanup_key_destructor`.
+//!
+//! `read_tsc_core` uses the serializing `__rdtscp` on x86_64 so readings
+//! can't be reordered across the boundary they're meant to measure, and
+//! returns a core id alongside the cycle count so a delta spanning a core
+//! migration can be detected and discarded (see `account_duration`) instead
+//! of silently reporting a bogus duration. `record_program_start`
+//! calibrates a cycles-to-nanoseconds ratio once (`calibrate_cycles_per_ns`)
+//! so `dump_stats` can report wall-clock time alongside raw cycles.
+//!
+//! Every unsafe block pays for `read_tsc_core` calls on entry (two in
+//! `cpu_cycle_start_measurement`: one in `transition_state`, one for the
+//! returned start value) and on exit (one in `cpu_cycle_end_measurement`),
+//! which can perturb the very hot loops this library is meant to measure.
+//! `UNSAFE_SAMPLE_RATE` (`[0.0, 1.0]`, default `1.0`) lets `transition_state`
+//! draw a per-block decision from a cheap per-thread xorshift RNG
+//! (`sample_decision`) and skip those reads entirely for blocks it didn't
+//! select, recording the decision on the frame (`ContextFrame::timed`) so
+//! the matching exit skips too. `dump_stats` reports the unsafe cycles of
+//! the timed blocks as measured, next to the timed/total block counts; it
+//! does not extrapolate them to the blocks that were not timed.
+//!
+//! The aggregate counters `dump_stats` reports collapse every thread into
+//! one line, which loses per-thread attribution for multi-threaded runs.
+//! `collect_per_thread_stats` walks the registry and returns a record per
+//! thread holding a slot (running or terminated); both the default plaintext output and the `CPU_CYCLE_FORMAT
+//! =json` path (`json_stats`) render from it, so a benchmark harness can
+//! select whichever is easier to parse without the two ever disagreeing on
+//! what a thread's counters are.
 
-use ctor::dtor;
 use lazy_static::lazy_static;
 use std::cell::Cell;
 use std::ffi::c_void;
@@ -38,7 +72,22 @@ struct ThreadStats {
     unsafe_blocks: AtomicU64,
     external_calls: AtomicU64,
 
-    _padding: [u64; 2],
+    // Intervals dropped because the thread migrated cores between the
+    // frame's start and the accounting read (see `read_tsc_core`), so the
+    // raw cycle delta can't be trusted.
+    discarded_intervals: AtomicU64,
+
+    // `UNSAFE_SAMPLE_RATE` bookkeeping (see `transition_state`): every plain
+    // `Unsafe` block entered bumps `sampled_total_blocks`; the subset
+    // actually drawn for precise timing additionally bumps `sampled_blocks`
+    // and accumulates its real duration into `sampled_cycles`. `unsafe_cycles`
+    // only reflects that timed subset when the rate is below `1.0`.
+    // `dump_stats`'s text output reports `sampled_blocks` and
+    // `sampled_total_blocks` as-is (`Unsafe blocks timed`) so a caller can
+    // gauge the sampling ratio; `sampled_cycles` is not reported.
+    sampled_total_blocks: AtomicU64,
+    sampled_blocks: AtomicU64,
+    sampled_cycles: AtomicU64,
 }
 
 impl ThreadStats {
@@ -54,7 +103,10 @@ impl ThreadStats {
             external_unsafe_cycles: AtomicU64::new(0),
             unsafe_blocks: AtomicU64::new(0),
             external_calls: AtomicU64::new(0),
-            _padding: [0; 2],
+            discarded_intervals: AtomicU64::new(0),
+            sampled_total_blocks: AtomicU64::new(0),
+            sampled_blocks: AtomicU64::new(0),
+            sampled_cycles: AtomicU64::new(0),
         }
     }
 }
@@ -101,6 +153,10 @@ impl ThreadRegistry {
                     stats.external_unsafe_cycles.store(0, Ordering::Relaxed);
                     stats.unsafe_blocks.store(0, Ordering::Relaxed);
                     stats.external_calls.store(0, Ordering::Relaxed);
+                    stats.discarded_intervals.store(0, Ordering::Relaxed);
+                    stats.sampled_total_blocks.store(0, Ordering::Relaxed);
+                    stats.sampled_blocks.store(0, Ordering::Relaxed);
+                    stats.sampled_cycles.store(0, Ordering::Relaxed);
                     return Some(slot);
                 }
             }
@@ -135,29 +191,165 @@ enum ExecutionState {
 struct ContextFrame {
     state: ExecutionState,
     start_tsc: u64,
+    // Core id `read_tsc_core` observed when this frame started. A mismatch
+    // against the core id at accounting time means the raw cycle delta
+    // spans a core migration and can't be trusted (see `finalize_slot` and
+    // friends, which discard such intervals instead).
+    core_id: u32,
+    // Whether this frame was selected by `UNSAFE_SAMPLE_RATE` for precise
+    // timing when it became `Unsafe` (see `transition_state`). Irrelevant
+    // for other states; defaults to `true` so a frame nobody samples still
+    // behaves like the pre-sampling code.
+    timed: bool,
 }
 
 thread_local! {
     static THREAD_SLOT: Cell<Option<usize>> = Cell::new(None);
-    static CONTEXT_STACK: Cell<[ContextFrame; MAX_CONTEXT_DEPTH]> = Cell::new([ContextFrame { state: ExecutionState::Normal, start_tsc: 0 }; MAX_CONTEXT_DEPTH]);
+    static CONTEXT_STACK: Cell<[ContextFrame; MAX_CONTEXT_DEPTH]> = Cell::new([ContextFrame { state: ExecutionState::Normal, start_tsc: 0, core_id: 0, timed: true }; MAX_CONTEXT_DEPTH]);
     static STACK_DEPTH: Cell<usize> = Cell::new(0);
 }
 
+/// Read the cycle counter together with an opaque "which core was this"
+/// value, so callers can detect a migration between a frame's start and the
+/// point it gets accounted for (see `ContextFrame::core_id`).
 #[inline(always)]
-fn read_tsc() -> u64 {
+fn read_tsc_core() -> (u64, u32) {
     #[cfg(target_arch = "x86_64")]
     unsafe {
-        core::arch::x86_64::_rdtsc()
+        // `__rdtscp` is a serializing read (unlike plain `_rdtsc`), so it
+        // can't be reordered past surrounding instructions by the CPU, and
+        // it returns `IA32_TSC_AUX` (aux), which Linux sets to an opaque
+        // value encoding the current core, letting us detect migration.
+        let mut aux: u32 = 0;
+        let cycles = core::arch::x86_64::__rdtscp(&mut aux);
+        (cycles, aux)
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        let cnt: u64;
+        // cntvct_el0: the architected virtual counter, free-running at the
+        // fixed frequency reported by cntfrq_el0 (see `tsc_frequency_hz`)
+        // and kept in sync across cores by the architecture, so there's no
+        // per-core migration hazard to detect here.
+        core::arch::asm!("mrs {0}, cntvct_el0", out(reg) cnt, options(nomem, nostack));
+        (cnt, 0)
     }
-    #[cfg(not(target_arch = "x86_64"))]
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
     {
-        std::time::SystemTime::now()
+        let ns = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
-            .as_nanos() as u64
+            .as_nanos() as u64;
+        (ns, 0)
+    }
+}
+
+/// Cycle counter only, for callers that don't need migration detection
+/// (e.g. handing an opaque start value across the C ABI).
+#[inline(always)]
+fn read_tsc() -> u64 {
+    read_tsc_core().0
+}
+
+/// aarch64's fixed tick frequency for `cntvct_el0`, in Hz, cached after the
+/// first read since `cntfrq_el0` is fixed for the life of the process. `0`
+/// means "not yet read". Lets a nanosecond-reporting mode convert `read_tsc`
+/// deltas without re-reading the system register on every call.
+#[cfg(target_arch = "aarch64")]
+static CNTFRQ_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// Returns aarch64's `cntvct_el0` tick frequency in Hz, reading `cntfrq_el0`
+/// once and caching the result.
+#[cfg(target_arch = "aarch64")]
+#[inline]
+fn tsc_frequency_hz() -> u64 {
+    let cached = CNTFRQ_HZ.load(Ordering::Relaxed);
+    if cached != 0 {
+        return cached;
+    }
+
+    let freq: u64;
+    unsafe {
+        core::arch::asm!("mrs {0}, cntfrq_el0", out(reg) freq, options(nomem, nostack));
+    }
+    CNTFRQ_HZ.store(freq, Ordering::Relaxed);
+    freq
+}
+
+/// Nanoseconds per cycle, as a Q32.32 fixed-point ratio (`ns = (cycles *
+/// CYCLES_TO_NS_SCALE) >> 32`), so `dump_stats` can report wall-clock time
+/// next to raw cycles. `0` means "not yet calibrated" (or calibration
+/// failed), in which case `cycles_to_ns` reports 0.
+static CYCLES_TO_NS_SCALE: AtomicU64 = AtomicU64::new(0);
+
+/// Ensures `calibrate_cycles_per_ns` runs exactly once.
+static CALIBRATED: AtomicBool = AtomicBool::new(false);
+
+/// Read `CLOCK_MONOTONIC` in nanoseconds, the same clock `clock_gettime`
+/// uses, for calibrating the cycle counter against wall-clock time.
+#[cfg(target_family = "unix")]
+fn monotonic_ns() -> u64 {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+#[cfg(not(target_family = "unix"))]
+fn monotonic_ns() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64
+}
+
+/// Establish `CYCLES_TO_NS_SCALE` once, at process start. On aarch64 this
+/// is exact and free: `cntfrq_el0` already gives the counter's fixed
+/// frequency. Elsewhere (x86_64, and the generic fallback, where `read_tsc`
+/// already reports nanoseconds) it brackets two `clock_gettime` /
+/// `read_tsc_core` pairs ~10ms apart and takes the ratio.
+fn calibrate_cycles_per_ns() {
+    if CALIBRATED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        let freq = tsc_frequency_hz();
+        if freq > 0 {
+            let scale = (1_000_000_000u128 << 32) / freq as u128;
+            CYCLES_TO_NS_SCALE.store(scale as u64, Ordering::Relaxed);
+        }
+        return;
+    }
+
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        let ns_start = monotonic_ns();
+        let cycles_start = read_tsc_core().0;
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let ns_end = monotonic_ns();
+        let cycles_end = read_tsc_core().0;
+
+        let ns_elapsed = ns_end.saturating_sub(ns_start);
+        let cycles_elapsed = cycles_end.saturating_sub(cycles_start);
+        if cycles_elapsed > 0 {
+            let scale = ((ns_elapsed as u128) << 32) / cycles_elapsed as u128;
+            CYCLES_TO_NS_SCALE.store(scale as u64, Ordering::Relaxed);
+        }
     }
 }
 
+/// Convert a cycle count to nanoseconds using the calibrated
+/// `CYCLES_TO_NS_SCALE`. Returns 0 if calibration never ran or failed.
+fn cycles_to_ns(cycles: u64) -> u64 {
+    let scale = CYCLES_TO_NS_SCALE.load(Ordering::Relaxed);
+    if scale == 0 {
+        return 0;
+    }
+    ((cycles as u128 * scale as u128) >> 32) as u64
+}
+
 /// Initializes tracking for the current thread, allocating a slot in the registry.
 fn initialize_thread() -> Option<usize> {
     THREAD_SLOT.with(|slot_cell| {
@@ -166,7 +358,7 @@ fn initialize_thread() -> Option<usize> {
         }
 
         if let Some(slot) = REGISTRY.allocate_slot() {
-            let tsc = read_tsc();
+            let (tsc, core_id) = read_tsc_core();
             let stats = &REGISTRY.threads[slot];
             stats.thread_id.store(get_thread_id(), Ordering::Relaxed);
             stats.start_tsc.store(tsc, Ordering::Relaxed);
@@ -176,11 +368,21 @@ fn initialize_thread() -> Option<usize> {
             // Initialize context stack with Normal state
             CONTEXT_STACK.with(|stack_cell| {
                 let mut stack = stack_cell.get();
-                stack[0] = ContextFrame { state: ExecutionState::Normal, start_tsc: tsc };
+                stack[0] = ContextFrame { state: ExecutionState::Normal, start_tsc: tsc, core_id, timed: true };
                 stack_cell.set(stack);
             });
             STACK_DEPTH.with(|depth_cell| depth_cell.set(1));
 
+            // Register this thread's slot with the pthread key so the
+            // destructor below is the authoritative finalizer even for
+            // threads that never go through `thread_start_wrapper` (the
+            // main thread, threads spawned via raw `clone()`, threads from
+            // a different interposition layer). Store `slot + 1` so 0 can
+            // keep meaning "no value" to `pthread_getspecific`.
+            unsafe {
+                libc::pthread_setspecific(*CLEANUP_KEY, (slot + 1) as *mut c_void);
+            }
+
             slot_cell.set(Some(slot));
             Some(slot)
         } else {
@@ -202,14 +404,124 @@ fn get_thread_id() -> u64 {
     }
 }
 
-/// Atomic state transition with cycle accounting
+/// Add `duration` to the counter for `state`, unless `frame_core` and
+/// `current_core` disagree — in which case the thread migrated cores
+/// between the frame's start and this accounting read, the raw cycle delta
+/// can't be trusted, and the interval is dropped (bumping
+/// `discarded_intervals` instead).
+fn account_duration(stats: &ThreadStats, state: ExecutionState, duration: u64, frame_core: u32, current_core: u32) {
+    if frame_core != current_core {
+        stats.discarded_intervals.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+
+    match state {
+        ExecutionState::Normal => stats.normal_cycles.fetch_add(duration, Ordering::Relaxed),
+        ExecutionState::Unsafe => stats.unsafe_cycles.fetch_add(duration, Ordering::Relaxed),
+        ExecutionState::ExternalSafe => stats.external_safe_cycles.fetch_add(duration, Ordering::Relaxed),
+        ExecutionState::ExternalUnsafe => stats.external_unsafe_cycles.fetch_add(duration, Ordering::Relaxed),
+    };
+}
+
+/// `UNSAFE_SAMPLE_RATE`, in `[0.0, 1.0]`, read once from the environment and
+/// cached as the bit pattern of the parsed `f64` (`u64::MAX`, not a valid
+/// `f64` bit pattern produced by `clamp`, marks "not read yet"). Defaults to
+/// `1.0` - every unsafe block timed, identical to the pre-sampling behavior
+/// - when unset, unparsable, or non-finite.
+static SAMPLE_RATE_BITS: AtomicU64 = AtomicU64::new(u64::MAX);
+
+fn sample_rate() -> f64 {
+    let bits = SAMPLE_RATE_BITS.load(Ordering::Relaxed);
+    if bits != u64::MAX {
+        return f64::from_bits(bits);
+    }
+
+    let rate = std::env::var("UNSAFE_SAMPLE_RATE")
+        .ok()
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|r| r.is_finite())
+        .map(|r| r.clamp(0.0, 1.0))
+        .unwrap_or(1.0);
+
+    SAMPLE_RATE_BITS.store(rate.to_bits(), Ordering::Relaxed);
+    rate
+}
+
+thread_local! {
+    /// Per-thread xorshift64 state backing `sample_decision`. `0` means
+    /// "not yet seeded".
+    static SAMPLE_RNG: Cell<u64> = Cell::new(0);
+}
+
+/// Cheap, non-cryptographic per-thread PRNG used only to decide whether an
+/// unsafe block gets precisely timed; seeded lazily from the thread id and a
+/// cycle reading so distinct threads don't share a stream.
+fn next_rand_u64() -> u64 {
+    SAMPLE_RNG.with(|cell| {
+        let mut x = cell.get();
+        if x == 0 {
+            x = get_thread_id() ^ read_tsc() ^ 0x9E37_79B9_7F4A_7C15;
+            if x == 0 {
+                x = 0xD1B5_4A32_D192_ED03;
+            }
+        }
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        cell.set(x);
+        x
+    })
+}
+
+/// Draw once against `rate` to decide whether the unsafe block being
+/// entered should be precisely timed. `rate >= 1.0` and `rate <= 0.0` are
+/// special-cased so the obvious answer doesn't depend on float rounding.
+fn sample_decision(rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    if rate <= 0.0 {
+        return false;
+    }
+    let r = (next_rand_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64);
+    r < rate
+}
+
+/// Whether the current top-of-stack frame was selected for precise timing
+/// (see `transition_state`). Defaults to `true` when the stack is empty so
+/// callers fail open to the pre-sampling behavior.
+fn current_frame_timed() -> bool {
+    CONTEXT_STACK.with(|stack_cell| {
+        STACK_DEPTH.with(|depth_cell| {
+            let depth = depth_cell.get();
+            if depth == 0 {
+                return true;
+            }
+            stack_cell.get()[depth - 1].timed
+        })
+    })
+}
+
+/// Atomic state transition with cycle accounting.
+///
+/// Entering or leaving plain `Unsafe` (as opposed to `ExternalUnsafe`, which
+/// is never sampled) is additionally subject to `UNSAFE_SAMPLE_RATE`: on
+/// entry, `sample_decision` draws once and the result is stashed on the
+/// frame's `timed` field; the matching exit reads it back so both ends take
+/// the same branch. When a block isn't timed, the `read_tsc_core` pair (and
+/// its `account_duration` accounting) is skipped entirely - this is the
+/// overhead the feature exists to cut, so `unsafe_cycles` only reflects the
+/// blocks that were actually timed rather than every block entered;
+/// `dump_stats` reports that raw, un-extrapolated figure, and a caller that
+/// cares about the sampling ratio can cross-reference `sampled_blocks`/
+/// `sampled_total_blocks` itself. At the default rate of `1.0` every block
+/// is timed, so this is exactly the pre-sampling behavior.
 fn transition_state(new_state: ExecutionState) -> Result<(), &'static str> {
     let slot = match THREAD_SLOT.with(|s| s.get()).or_else(initialize_thread) {
         Some(slot) => slot,
         None => return Err("Thread not initialized"),
     };
 
-    let current_tsc = read_tsc();
     let stats = &REGISTRY.threads[slot];
 
     CONTEXT_STACK.with(|stack_cell| {
@@ -222,21 +534,44 @@ fn transition_state(new_state: ExecutionState) -> Result<(), &'static str> {
             let mut stack = stack_cell.get();
             let current_frame = &mut stack[depth - 1];
 
-            // Account for time spent in current state
+            let entering_unsafe = new_state == ExecutionState::Unsafe && current_frame.state != ExecutionState::Unsafe;
+            let leaving_unsafe = current_frame.state == ExecutionState::Unsafe && new_state != ExecutionState::Unsafe;
+
+            let timed = if entering_unsafe {
+                stats.sampled_total_blocks.fetch_add(1, Ordering::Relaxed);
+                sample_decision(sample_rate())
+            } else {
+                current_frame.timed
+            };
+
+            if !timed {
+                // Skip the read_tsc_core pair (and its accounting) entirely
+                // for a block not selected for timing - paying for a
+                // serializing TSC read on every unsafe block is exactly the
+                // overhead UNSAFE_SAMPLE_RATE exists to cut.
+                current_frame.state = new_state;
+                current_frame.timed = timed;
+                stack_cell.set(stack);
+                return Ok(());
+            }
+
+            let (current_tsc, current_core) = read_tsc_core();
+
+            // Account for time spent in current state.
             if current_frame.start_tsc > 0 && current_tsc > current_frame.start_tsc {
                 let duration = current_tsc - current_frame.start_tsc;
-
-                match current_frame.state {
-                    ExecutionState::Normal => stats.normal_cycles.fetch_add(duration, Ordering::Relaxed),
-                    ExecutionState::Unsafe => stats.unsafe_cycles.fetch_add(duration, Ordering::Relaxed),
-                    ExecutionState::ExternalSafe => stats.external_safe_cycles.fetch_add(duration, Ordering::Relaxed),
-                    ExecutionState::ExternalUnsafe => stats.external_unsafe_cycles.fetch_add(duration, Ordering::Relaxed),
-                };
+                account_duration(stats, current_frame.state, duration, current_frame.core_id, current_core);
+                if leaving_unsafe {
+                    stats.sampled_cycles.fetch_add(duration, Ordering::Relaxed);
+                    stats.sampled_blocks.fetch_add(1, Ordering::Relaxed);
+                }
             }
 
             // Update current frame to new state
             current_frame.state = new_state;
             current_frame.start_tsc = current_tsc;
+            current_frame.core_id = current_core;
+            current_frame.timed = timed;
 
             stack_cell.set(stack);
             Ok(())
@@ -251,7 +586,7 @@ fn push_context(new_state: ExecutionState) -> Result<(), &'static str> {
         None => return Err("Thread not initialized"),
     };
 
-    let current_tsc = read_tsc();
+    let (current_tsc, current_core) = read_tsc_core();
     let stats = &REGISTRY.threads[slot];
 
     CONTEXT_STACK.with(|stack_cell| {
@@ -268,18 +603,12 @@ fn push_context(new_state: ExecutionState) -> Result<(), &'static str> {
                 let current_frame = &mut stack[depth - 1];
                 if current_frame.start_tsc > 0 && current_tsc > current_frame.start_tsc {
                     let duration = current_tsc - current_frame.start_tsc;
-
-                    match current_frame.state {
-                        ExecutionState::Normal => stats.normal_cycles.fetch_add(duration, Ordering::Relaxed),
-                        ExecutionState::Unsafe => stats.unsafe_cycles.fetch_add(duration, Ordering::Relaxed),
-                        ExecutionState::ExternalSafe => stats.external_safe_cycles.fetch_add(duration, Ordering::Relaxed),
-                        ExecutionState::ExternalUnsafe => stats.external_unsafe_cycles.fetch_add(duration, Ordering::Relaxed),
-                    };
+                    account_duration(stats, current_frame.state, duration, current_frame.core_id, current_core);
                 }
             }
 
             // Push new context
-            stack[depth] = ContextFrame { state: new_state, start_tsc: current_tsc };
+            stack[depth] = ContextFrame { state: new_state, start_tsc: current_tsc, core_id: current_core, timed: true };
             depth_cell.set(depth + 1);
             stack_cell.set(stack);
             Ok(())
@@ -294,7 +623,7 @@ fn pop_context() -> Result<(), &'static str> {
         None => return Err("Thread not initialized"),
     };
 
-    let current_tsc = read_tsc();
+    let (current_tsc, current_core) = read_tsc_core();
     let stats = &REGISTRY.threads[slot];
 
     CONTEXT_STACK.with(|stack_cell| {
@@ -310,19 +639,14 @@ fn pop_context() -> Result<(), &'static str> {
             // Account for time in current state
             if current_frame.start_tsc > 0 && current_tsc > current_frame.start_tsc {
                 let duration = current_tsc - current_frame.start_tsc;
-
-                match current_frame.state {
-                    ExecutionState::Normal => stats.normal_cycles.fetch_add(duration, Ordering::Relaxed),
-                    ExecutionState::Unsafe => stats.unsafe_cycles.fetch_add(duration, Ordering::Relaxed),
-                    ExecutionState::ExternalSafe => stats.external_safe_cycles.fetch_add(duration, Ordering::Relaxed),
-                    ExecutionState::ExternalUnsafe => stats.external_unsafe_cycles.fetch_add(duration, Ordering::Relaxed),
-                };
+                account_duration(stats, current_frame.state, duration, current_frame.core_id, current_core);
             }
 
             // Pop context and resume previous state
             depth_cell.set(depth - 1);
             let previous_frame = &mut stack[depth - 2];
             previous_frame.start_tsc = current_tsc; // Reset timing for resumed context
+            previous_frame.core_id = current_core;
 
             stack_cell.set(stack);
             Ok(())
@@ -330,39 +654,97 @@ fn pop_context() -> Result<(), &'static str> {
     })
 }
 
-/// Marks the current thread as terminated and records its final timestamp.
+/// Accounts for a thread's final in-flight cycles and marks its slot
+/// `Terminated`. Guarded by a CAS on `state` so it is safe to call from both
+/// the fast path (`thread_cleanup`, via the `pthread_create` wrapper) and
+/// the authoritative path (`cleanup_key_destructor`) without double-counting
+/// whichever one gets there first.
+fn finalize_slot(slot: usize) {
+    if slot >= MAX_THREADS {
+        return;
+    }
+
+    let stats = &REGISTRY.threads[slot];
+    if stats
+        .state
+        .compare_exchange(
+            ThreadState::Active as usize,
+            ThreadState::Terminated as usize,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        // Already finalized (or never started) by the other path.
+        return;
+    }
+
+    let (final_tsc, final_core) = read_tsc_core();
+
+    // Account for any remaining time in current state. Safe to read from
+    // any thread (including, via the pthread key destructor, the dying
+    // thread itself after its own start routine has returned) since these
+    // thread-locals hold only `Copy` data and so never register a std
+    // destructor of their own.
+    CONTEXT_STACK.with(|stack_cell| {
+        STACK_DEPTH.with(|depth_cell| {
+            let depth = depth_cell.get();
+            if depth > 0 {
+                let stack = stack_cell.get();
+                let current_frame = &stack[depth - 1];
+
+                if current_frame.start_tsc > 0 && final_tsc > current_frame.start_tsc {
+                    let duration = final_tsc - current_frame.start_tsc;
+                    account_duration(stats, current_frame.state, duration, current_frame.core_id, final_core);
+                }
+            }
+        });
+    });
+
+    stats.last_known_tsc.store(final_tsc, Ordering::Release);
+}
+
+/// Fast-path finalizer: marks the *calling* thread's slot terminated. Used
+/// by `thread_start_wrapper` right after the user's start routine returns.
+/// Not authoritative on its own — see `cleanup_key_destructor`.
 fn thread_cleanup() {
     if let Some(slot) = THREAD_SLOT.with(|s| s.get()) {
-        if slot < MAX_THREADS {
-            let final_tsc = read_tsc();
-            let stats = &REGISTRY.threads[slot];
+        finalize_slot(slot);
+        // Clear the key's value so `cleanup_key_destructor` is not called
+        // for this thread: by then the slot may belong to another thread.
+        unsafe {
+            libc::pthread_setspecific(*CLEANUP_KEY, std::ptr::null_mut());
+        }
+    }
+}
 
-            // Account for any remaining time in current state
-            CONTEXT_STACK.with(|stack_cell| {
-                STACK_DEPTH.with(|depth_cell| {
-                    let depth = depth_cell.get();
-                    if depth > 0 {
-                        let stack = stack_cell.get();
-                        let current_frame = &stack[depth - 1];
-
-                        if current_frame.start_tsc > 0 && final_tsc > current_frame.start_tsc {
-                            let duration = final_tsc - current_frame.start_tsc;
-
-                            match current_frame.state {
-                                ExecutionState::Normal => stats.normal_cycles.fetch_add(duration, Ordering::Relaxed),
-                                ExecutionState::Unsafe => stats.unsafe_cycles.fetch_add(duration, Ordering::Relaxed),
-                                ExecutionState::ExternalSafe => stats.external_safe_cycles.fetch_add(duration, Ordering::Relaxed),
-                                ExecutionState::ExternalUnsafe => stats.external_unsafe_cycles.fetch_add(duration, Ordering::Relaxed),
-                            };
-                        }
-                    }
-                });
-            });
+/// Destructor registered against `CLEANUP_KEY`, invoked by the pthread
+/// implementation while a thread is exiting, regardless of whether it was
+/// ever routed through `thread_start_wrapper`. This is the authoritative
+/// finalizer: when the fast path already ran, `thread_cleanup` has cleared
+/// the key's value, so pthread does not call this at all; otherwise this is
+/// the real finalize (threads created via raw `clone()`, a different
+/// interposition layer, etc).
+extern "C" fn cleanup_key_destructor(value: *mut c_void) {
+    let raw = value as usize;
+    if raw == 0 {
+        return;
+    }
+    finalize_slot(raw - 1);
+}
 
-            stats.last_known_tsc.store(final_tsc, Ordering::Release);
-            stats.state.store(ThreadState::Terminated as usize, Ordering::Release);
+lazy_static! {
+    /// Process-wide pthread TSD key whose destructor is the authoritative
+    /// per-thread finalizer (see `cleanup_key_destructor`). Created lazily
+    /// on first use so there's exactly one key for the process lifetime.
+    static ref CLEANUP_KEY: libc::pthread_key_t = unsafe {
+        let mut key: libc::pthread_key_t = 0;
+        let rc = libc::pthread_key_create(&mut key, Some(cleanup_key_destructor));
+        if rc != 0 {
+            eprintln!("[Runtime] Warning: pthread_key_create failed (rc={}); per-thread finalization will rely on the pthread_create wrapper only.", rc);
         }
-    }
+        key
+    };
 }
 
 // ==========================================================================================
@@ -372,6 +754,7 @@ fn thread_cleanup() {
 #[no_mangle]
 pub extern "C" fn record_program_start() {
     initialize_thread();
+    calibrate_cycles_per_ns();
 }
 
 #[no_mangle]
@@ -401,12 +784,22 @@ pub extern "C" fn cpu_cycle_start_measurement() -> u64 {
         _ => current_state, // Already in unsafe or external_unsafe
     };
 
+    let mut timed = true;
     if transition_state(new_state).is_ok() {
         let stats = &REGISTRY.threads[slot];
         stats.unsafe_blocks.fetch_add(1, Ordering::Relaxed);
+        timed = current_frame_timed();
     }
 
-    read_tsc()
+    // Blocks `transition_state` didn't select for sampling already skipped
+    // their `read_tsc_core` pair internally; skip this second, separate
+    // read too rather than paying for it only to hand back a value
+    // `cpu_cycle_end_measurement` ignores anyway.
+    if timed {
+        read_tsc()
+    } else {
+        0
+    }
 }
 
 #[no_mangle]
@@ -540,19 +933,38 @@ pub extern "C" fn print_cpu_cycle_stats() {
     }
 }
 
-/// This function is registered to run when the program exits.
-#[dtor]
+/// This function is registered with the crate's unified shutdown
+/// coordinator (`register_at_exit`) to run at process exit.
+///
+/// Process exit never unwinds pthread TSD, so the main thread (and any
+/// other thread still alive at this point) never hits
+/// `cleanup_key_destructor`. Finalize the calling thread explicitly before
+/// computing totals; `finalize_slot`'s CAS makes this a safe no-op for any
+/// thread whose slot was already finalized.
 fn final_cleanup() {
+    thread_cleanup();
     print_cpu_cycle_stats();
 }
 
-fn calculate_total_stats() -> (u64, u64, u64, u64, u64, u64, u64) {
+/// Register `final_cleanup` with the crate's unified shutdown coordinator
+/// instead of installing our own `#[ctor::dtor]`, so CPU cycle stats flush
+/// in a single, ordered place alongside every other feature module.
+#[ctor::ctor]
+fn register_cpu_cycle_counter_shutdown() {
+    crate::register_at_exit(final_cleanup);
+}
+
+fn calculate_total_stats() -> (u64, u64, u64, u64, u64, u64, u64, u64, u64, u64, u64) {
     let mut total_normal = 0;
     let mut total_unsafe = 0;
     let mut total_external_safe = 0;
     let mut total_external_unsafe = 0;
     let mut total_unsafe_blocks = 0;
     let mut total_external_calls = 0;
+    let mut total_discarded_intervals = 0;
+    let mut total_sampled_total_blocks = 0;
+    let mut total_sampled_blocks = 0;
+    let mut total_sampled_cycles = 0;
 
     let max_slot = REGISTRY.next_slot.load(Ordering::Acquire);
     for slot in 0..max_slot.min(MAX_THREADS) {
@@ -568,19 +980,155 @@ fn calculate_total_stats() -> (u64, u64, u64, u64, u64, u64, u64) {
         total_external_unsafe += stats.external_unsafe_cycles.load(Ordering::Acquire);
         total_unsafe_blocks += stats.unsafe_blocks.load(Ordering::Acquire);
         total_external_calls += stats.external_calls.load(Ordering::Acquire);
+        total_discarded_intervals += stats.discarded_intervals.load(Ordering::Acquire);
+        total_sampled_total_blocks += stats.sampled_total_blocks.load(Ordering::Acquire);
+        total_sampled_blocks += stats.sampled_blocks.load(Ordering::Acquire);
+        total_sampled_cycles += stats.sampled_cycles.load(Ordering::Acquire);
     }
 
     let total_program_cycles = total_normal + total_unsafe + total_external_safe + total_external_unsafe;
 
-    (total_program_cycles, total_normal, total_unsafe, total_external_safe, total_external_unsafe, total_unsafe_blocks, total_external_calls)
+    (total_program_cycles, total_normal, total_unsafe, total_external_safe, total_external_unsafe,
+     total_unsafe_blocks, total_external_calls, total_discarded_intervals,
+     total_sampled_total_blocks, total_sampled_blocks, total_sampled_cycles)
+}
+
+/// A single thread's raw counters, as returned by `collect_per_thread_stats`.
+/// Shared by the text and JSON writers in `dump_stats` so they can't drift
+/// on which fields the registry walk exposes.
+struct PerThreadStats {
+    thread_id: u64,
+    normal_cycles: u64,
+    unsafe_cycles: u64,
+    external_safe_cycles: u64,
+    external_unsafe_cycles: u64,
+    unsafe_blocks: u64,
+    external_calls: u64,
+}
+
+/// Walk the registry and snapshot the counters of every thread holding a
+/// slot, running or terminated (mirrors the
+/// loop in `calculate_total_stats`, which only needs the sums). `unsafe_cycles`
+/// here is the same raw, un-extrapolated figure `dump_stats` reports at the
+/// aggregate level - callers that care about the `UNSAFE_SAMPLE_RATE` ratio
+/// should cross-reference `sampled_blocks`/`sampled_total_blocks` themselves.
+fn collect_per_thread_stats() -> Vec<PerThreadStats> {
+    let mut per_thread = Vec::new();
+    let max_slot = REGISTRY.next_slot.load(Ordering::Acquire);
+    for slot in 0..max_slot.min(MAX_THREADS) {
+        let stats = &REGISTRY.threads[slot];
+        if stats.state.load(Ordering::Acquire) == ThreadState::Uninitialized as usize {
+            continue;
+        }
+
+        per_thread.push(PerThreadStats {
+            thread_id: stats.thread_id.load(Ordering::Acquire),
+            normal_cycles: stats.normal_cycles.load(Ordering::Acquire),
+            unsafe_cycles: stats.unsafe_cycles.load(Ordering::Acquire),
+            external_safe_cycles: stats.external_safe_cycles.load(Ordering::Acquire),
+            external_unsafe_cycles: stats.external_unsafe_cycles.load(Ordering::Acquire),
+            unsafe_blocks: stats.unsafe_blocks.load(Ordering::Acquire),
+            external_calls: stats.external_calls.load(Ordering::Acquire),
+        });
+    }
+    per_thread
+}
+
+/// Selects how `dump_stats` renders its output. `CPU_CYCLE_FORMAT=json`
+/// switches to `json_stats`; anything else (including unset) keeps the
+/// original plaintext block for compatibility with existing harness scripts.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+fn output_format() -> OutputFormat {
+    match std::env::var("CPU_CYCLE_FORMAT").ok().as_deref() {
+        Some("json") => OutputFormat::Json,
+        _ => OutputFormat::Text,
+    }
+}
+
+/// JSON rendering: one object per `collect_per_thread_stats` record (so a benchmark harness can
+/// attribute cost per thread and diff across runs) plus a top-level totals
+/// object and the calibrated nanosecond conversion. Hand-rolled (no serde)
+/// to match this crate's other JSON writers.
+fn json_stats(
+    per_thread: &[PerThreadStats],
+    total_cycles: u64,
+    unsafe_cycles: u64,
+    external_cycles: u64,
+    internal_cycles: u64,
+    discarded_intervals: u64,
+) -> String {
+    let mut threads = String::new();
+    for (i, t) in per_thread.iter().enumerate() {
+        if i > 0 {
+            threads.push(',');
+        }
+        threads.push_str(&format!(
+            concat!(
+                "{{",
+                "\"thread_id\":{},",
+                "\"normal_cycles\":{},",
+                "\"unsafe_cycles\":{},",
+                "\"external_safe_cycles\":{},",
+                "\"external_unsafe_cycles\":{},",
+                "\"unsafe_blocks\":{},",
+                "\"external_calls\":{}",
+                "}}",
+            ),
+            t.thread_id, t.normal_cycles, t.unsafe_cycles, t.external_safe_cycles,
+            t.external_unsafe_cycles, t.unsafe_blocks, t.external_calls,
+        ));
+    }
+
+    format!(
+        concat!(
+            "{{",
+            "\"threads\":[{}],",
+            "\"totals\":{{",
+            "\"total_cycles\":{},",
+            "\"unsafe_cycles\":{},",
+            "\"external_cycles\":{},",
+            "\"internal_cycles\":{},",
+            "\"discarded_intervals\":{}",
+            "}},",
+            "\"nanoseconds\":{{",
+            "\"total\":{},",
+            "\"unsafe\":{},",
+            "\"external\":{},",
+            "\"internal\":{}",
+            "}}",
+            "}}\n",
+        ),
+        threads,
+        total_cycles, unsafe_cycles, external_cycles, internal_cycles, discarded_intervals,
+        cycles_to_ns(total_cycles), cycles_to_ns(unsafe_cycles),
+        cycles_to_ns(external_cycles), cycles_to_ns(internal_cycles),
+    )
 }
 
 fn dump_stats() {
-    let (total_cycles, normal_cycles, unsafe_cycles, external_safe_cycles, external_unsafe_cycles, _unsafe_blocks, _external_calls) = calculate_total_stats();
+    let (
+        _total_program_cycles,
+        normal_cycles,
+        unsafe_cycles,
+        external_safe_cycles,
+        external_unsafe_cycles,
+        _unsafe_blocks,
+        _external_calls,
+        discarded_intervals,
+        sampled_total_blocks,
+        sampled_blocks,
+        _sampled_cycles,
+    ) = calculate_total_stats();
 
     // Clean accounting - no overlaps, no double counting
     let internal_cycles = normal_cycles + unsafe_cycles;
     let external_cycles = external_safe_cycles + external_unsafe_cycles;
+    let total_cycles = internal_cycles + external_cycles;
 
     let unsafe_percentage = if internal_cycles > 0 {
         (unsafe_cycles as f64 / internal_cycles as f64) * 100.0
@@ -588,8 +1136,16 @@ fn dump_stats() {
         0.0
     };
 
+    let per_thread = collect_per_thread_stats();
+
+    if output_format() == OutputFormat::Json {
+        let json = json_stats(&per_thread, total_cycles, unsafe_cycles, external_cycles, internal_cycles, discarded_intervals);
+        let _ = write_output(&json, "cpu_cycle.json");
+        return;
+    }
+
     // Create structured output for script parsing
-    let output = format!(
+    let mut output = format!(
         concat!(
             "\n===== CPU Cycle Statistics =====\n",
             "Total cycles: {}\n",
@@ -597,10 +1153,28 @@ fn dump_stats() {
             "External cycles: {}\n",
             "Internal cycles: {}\n",
             "Unsafe percentage: {:.2}\n",
+            "Total nanoseconds: {}\n",
+            "Unsafe nanoseconds: {}\n",
+            "External nanoseconds: {}\n",
+            "Internal nanoseconds: {}\n",
+            "Discarded intervals (core migration): {}\n",
+            "Unsafe blocks timed: {}/{}\n",
         ),
-        total_cycles, unsafe_cycles, external_cycles, internal_cycles, unsafe_percentage
+        total_cycles, unsafe_cycles, external_cycles, internal_cycles, unsafe_percentage,
+        cycles_to_ns(total_cycles), cycles_to_ns(unsafe_cycles), cycles_to_ns(external_cycles), cycles_to_ns(internal_cycles),
+        discarded_intervals,
+        sampled_blocks, sampled_total_blocks,
     );
 
+    output.push_str(&format!("Per-thread stats ({} threads):\n", per_thread.len()));
+    for t in &per_thread {
+        output.push_str(&format!(
+            "  thread={} normal={} unsafe={} ext_safe={} ext_unsafe={} unsafe_blocks={} external_calls={}\n",
+            t.thread_id, t.normal_cycles, t.unsafe_cycles, t.external_safe_cycles,
+            t.external_unsafe_cycles, t.unsafe_blocks, t.external_calls,
+        ));
+    }
+
     // Write structured output to file for script parsing
     let _ = write_output(&output, "cpu_cycle.stat");
 }
@@ -1,11 +1,110 @@
 //! Collecting stats about heap memory objects.
 
 use std::alloc::{GlobalAlloc, Layout, System};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::collections::{BTreeMap, BTreeSet};
+use std::backtrace::Backtrace;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::sync::Mutex;
 use std::cell::Cell;
-use crate::write_output;
+use crate::{write_output, write_record, structured_output_enabled, JsonValue};
+
+/// Whether allocation-site backtrace capture (`HEAP_BACKTRACE=1`) is on.
+/// Unwinding on every allocation is too expensive to do unconditionally, so
+/// this is opt-in and read once from the environment. `usize::MAX` means
+/// "not read yet".
+static BACKTRACE_ENABLED: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+fn backtrace_enabled() -> bool {
+    let cached = BACKTRACE_ENABLED.load(Ordering::Relaxed);
+    if cached != usize::MAX {
+        return cached != 0;
+    }
+
+    let enabled = std::env::var("HEAP_BACKTRACE").map(|v| v == "1").unwrap_or(false);
+    BACKTRACE_ENABLED.store(enabled as usize, Ordering::Relaxed);
+    enabled
+}
+
+/// Number of lines of the rendered backtrace (not frames) kept per
+/// allocation site. Keeps the string
+/// used to group unsafe bytes by site short enough to aggregate
+/// meaningfully, rather than every allocation producing its own unique key.
+const BACKTRACE_FRAME_LIMIT: usize = 8;
+
+/// Capture the current call stack and render its first
+/// `BACKTRACE_FRAME_LIMIT` lines as the allocation-site key.
+fn capture_site() -> String {
+    Backtrace::force_capture()
+        .to_string()
+        .lines()
+        .take(BACKTRACE_FRAME_LIMIT)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Maximum number of samples `HeapTracker::timeline` keeps; once full, the
+/// oldest sample is dropped to make room for the newest (a bounded ring
+/// buffer rather than an unbounded log that could itself become the leak).
+const HEAP_TIMELINE_CAPACITY: usize = 4096;
+
+/// Whether the opt-in time-series profiling mode (`HEAP_TIMELINE=1`) is on.
+/// Read once from the environment and cached; `usize::MAX` means "not read
+/// yet".
+static TIMELINE_ENABLED: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+fn timeline_enabled() -> bool {
+    let cached = TIMELINE_ENABLED.load(Ordering::Relaxed);
+    if cached != usize::MAX {
+        return cached != 0;
+    }
+
+    let enabled = std::env::var("HEAP_TIMELINE").map(|v| v == "1").unwrap_or(false);
+    TIMELINE_ENABLED.store(enabled as usize, Ordering::Relaxed);
+    enabled
+}
+
+/// Size (in bytes) at or above which an allocation is classified as "large"
+/// in `classify_obj_by_size` rather than "small". Configurable via
+/// `MMAP_THRESHOLD_BYTES`; defaults to 512 KB, the crossover glibc's
+/// sbrk-vs-mmap allocation strategy uses, since small and large allocations
+/// have very different allocator cost profiles. Read once and cached; `0`
+/// means "not read yet".
+static MMAP_THRESHOLD: AtomicUsize = AtomicUsize::new(0);
+
+fn mmap_threshold() -> usize {
+    let cached = MMAP_THRESHOLD.load(Ordering::Relaxed);
+    if cached != 0 {
+        return cached;
+    }
+
+    let threshold = std::env::var("MMAP_THRESHOLD_BYTES")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(512 * 1024);
+    MMAP_THRESHOLD.store(threshold, Ordering::Relaxed);
+    threshold
+}
+
+/// Number of alloc/dealloc/realloc events between timeline samples,
+/// configured by `HEAP_TIMELINE_INTERVAL` (default 1000 events). Read once
+/// from the environment and cached; `0` means "not read yet".
+static TIMELINE_INTERVAL: AtomicUsize = AtomicUsize::new(0);
+
+fn timeline_interval() -> usize {
+    let cached = TIMELINE_INTERVAL.load(Ordering::Relaxed);
+    if cached != 0 {
+        return cached;
+    }
+
+    let interval = std::env::var("HEAP_TIMELINE_INTERVAL")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(1000);
+    TIMELINE_INTERVAL.store(interval, Ordering::Relaxed);
+    interval
+}
 
 thread_local! {
     /// Thread-local flag to skip tracking allocations made by the heap
@@ -20,6 +119,94 @@ thread_local! {
     static SKIP_TRACKING: Cell<bool> = Cell::new(false);
 }
 
+/// Maximum number of distinct threads that can hold a counter shard
+/// concurrently. Mirrors the cap used by `unsafe_counter`'s and
+/// `cpu_cycle_counter`'s own per-thread tables.
+const MAX_THREADS: usize = 4096;
+
+/// Per-thread mirror of this tracker's hottest counters: `total_mem_insts`,
+/// `unsafe_load` and `unsafe_store` are bumped on *every* instrumented memory
+/// access (`dyn_mem_access`/`dyn_unsafe_mem_access`), so routing them through
+/// the shared atomics directly would serialize every unsafe memory access in
+/// the program behind a single cache line. Each thread instead accumulates
+/// into its own claimed `Shard` and only the final fold (at shutdown, or
+/// early via `__heap_flush_thread`) touches the global totals.
+///
+/// The other fields on `HeapTracker` (live object maps, peak high-water
+/// marks, size histograms, leak/timeline/site bookkeeping) stay global: they
+/// are either already behind a `Mutex` for a cross-thread range lookup, or
+/// are a max rather than a sum and so don't shard without losing precision.
+#[repr(align(64))]
+struct Shard {
+    claimed: AtomicBool,
+    total_mem_insts: AtomicUsize,
+    unsafe_load: AtomicUsize,
+    unsafe_store: AtomicUsize,
+}
+
+impl Shard {
+    const fn new() -> Self {
+        Self {
+            claimed: AtomicBool::new(false),
+            total_mem_insts: AtomicUsize::new(0),
+            unsafe_load: AtomicUsize::new(0),
+            unsafe_store: AtomicUsize::new(0),
+        }
+    }
+
+    fn reset(&self) {
+        self.total_mem_insts.store(0, Ordering::Relaxed);
+        self.unsafe_load.store(0, Ordering::Relaxed);
+        self.unsafe_store.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Fixed, const-initialized table of shards - no allocation, `MAX_THREADS` cap.
+static SHARDS: [Shard; MAX_THREADS] = [const { Shard::new() }; MAX_THREADS];
+
+/// One past the highest shard slot ever claimed.
+static NEXT_SHARD_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// This thread's claimed shard slot, if any.
+    static THREAD_SHARD_SLOT: Cell<Option<usize>> = Cell::new(None);
+}
+
+/// Return this thread's shard, claiming a fresh slot (or reusing a flushed
+/// one) on first use. Falls back to sharing slot 0 once `MAX_THREADS` is
+/// exceeded, which reintroduces contention only for that rare overflow case.
+fn current_shard() -> &'static Shard {
+    THREAD_SHARD_SLOT.with(|slot_cell| {
+        if let Some(i) = slot_cell.get() {
+            return &SHARDS[i];
+        }
+
+        // Try to reclaim a shard left behind by a thread that flushed/exited.
+        let claimed_so_far = NEXT_SHARD_SLOT.load(Ordering::Acquire).min(MAX_THREADS);
+        for i in 0..claimed_so_far {
+            if SHARDS[i].claimed.compare_exchange(
+                false, true, Ordering::AcqRel, Ordering::Acquire
+            ).is_ok() {
+                SHARDS[i].reset();
+                slot_cell.set(Some(i));
+                return &SHARDS[i];
+            }
+        }
+
+        // Otherwise claim the next never-used slot.
+        let i = NEXT_SHARD_SLOT.fetch_add(1, Ordering::Relaxed);
+        if i < MAX_THREADS {
+            SHARDS[i].claimed.store(true, Ordering::Release);
+            slot_cell.set(Some(i));
+            &SHARDS[i]
+        } else {
+            NEXT_SHARD_SLOT.fetch_sub(1, Ordering::Relaxed);
+            slot_cell.set(Some(0));
+            &SHARDS[0]
+        }
+    })
+}
+
 /// We classify heap objects into 14 groups by size:
 /// - <= 1KB
 /// - > 1KB && <= 2KB
@@ -44,18 +231,29 @@ const ATOMICUSIZE_INIT_0: AtomicUsize = AtomicUsize::new(0);
 struct HeapTracker {
     // Total allocated heap objects in bytes.
     total_usage: AtomicUsize,
+    // High-water mark of total_usage: unlike total_usage, which is
+    // decremented on dealloc/realloc-shrink and so only reflects whatever is
+    // still live, this never goes down, giving the true peak footprint a
+    // caller needs for sizing an allocator (see `update_peak`).
+    peak_usage: AtomicUsize,
     // Total number of times allocating a heap object (including realloc)
     total_alloc: AtomicUsize,
     // Total number of times reallocating a heap object (including realloc)
     total_realloc: AtomicUsize,
     // Total number of times deallocating a heap object (including realloc)
     total_dealloc: AtomicUsize,
+    // Number of dealloc/realloc calls on an address not present in
+    // live_objs: a double-free, or a free of an object this tracker never
+    // saw allocated. Mirrors mockalloc's double-free flag.
+    invalid_frees: AtomicUsize,
     // A BTreeMap containing ranges of all active heap objects
     live_objs: Mutex<BTreeMap<usize, usize>>,
     // Total number of unsafe heap objects.
     total_unsafe_objs: AtomicUsize,
     // Accumulated heap memory accessed by unsafe code
     unsafe_mem: AtomicUsize,
+    // High-water mark of unsafe_mem. See peak_usage.
+    peak_unsafe_mem: AtomicUsize,
     // A set of live unsafe heap objects, represented by their starting addresses.
     live_unsafe_objs: Mutex<BTreeSet<usize>>,
     // Total number of heap memory access
@@ -68,24 +266,65 @@ struct HeapTracker {
     size_histogram: [AtomicUsize; OBJ_SIZE_NUM],
     // Histogram of unsafe object sizes
     unsafe_size_histogram: [AtomicUsize; OBJ_SIZE_NUM],
+    // Count and byte total of allocations below mmap_threshold().
+    small_alloc: AtomicUsize,
+    small_bytes: AtomicUsize,
+    // Count and byte total of allocations at or above mmap_threshold().
+    large_alloc: AtomicUsize,
+    large_bytes: AtomicUsize,
+    // Same split, but only for allocations classified as unsafe.
+    unsafe_small_alloc: AtomicUsize,
+    unsafe_small_bytes: AtomicUsize,
+    unsafe_large_alloc: AtomicUsize,
+    unsafe_large_bytes: AtomicUsize,
+    // Event counter driving the HEAP_TIMELINE sampling cadence: every
+    // alloc/dealloc/realloc bumps this, and a sample is recorded whenever it
+    // lands on `timeline_interval()`.
+    event_counter: AtomicUsize,
+    // Bounded ring buffer of (event, total_usage, unsafe_mem, live_obj_count)
+    // samples for the opt-in HEAP_TIMELINE time-series profiling mode.
+    timeline: Mutex<VecDeque<(usize, usize, usize, usize)>>,
+    // base_addr -> short resolved call stack, captured at alloc/realloc time
+    // when HEAP_BACKTRACE=1 is set. Only as many entries as there are live
+    // objects; pruned in remove_obj.
+    alloc_sites: Mutex<BTreeMap<usize, String>>,
+    // allocation-site string -> (unsafe bytes attributed, unsafe object
+    // count), accumulated the first time access_unsafe_heap_obj promotes an
+    // object at that site into live_unsafe_objs.
+    site_unsafe_bytes: Mutex<BTreeMap<String, (usize, usize)>>,
 }
 
 impl HeapTracker {
     pub const fn new() -> Self {
         Self {
             total_usage: AtomicUsize::new(0),
+            peak_usage: AtomicUsize::new(0),
             total_alloc: AtomicUsize::new(0),
             total_realloc: AtomicUsize::new(0),
             total_dealloc: AtomicUsize::new(0),
+            invalid_frees: AtomicUsize::new(0),
             live_objs: Mutex::new(BTreeMap::new()),
             total_unsafe_objs: AtomicUsize::new(0),
             unsafe_mem: AtomicUsize::new(0),
+            peak_unsafe_mem: AtomicUsize::new(0),
             live_unsafe_objs: Mutex::new(BTreeSet::new()),
             total_mem_insts: ATOMICUSIZE_INIT_0,
             unsafe_load: AtomicUsize::new(0),
             unsafe_store: AtomicUsize::new(0),
             size_histogram: [ATOMICUSIZE_INIT_0; OBJ_SIZE_NUM],
             unsafe_size_histogram: [ATOMICUSIZE_INIT_0; OBJ_SIZE_NUM],
+            small_alloc: AtomicUsize::new(0),
+            small_bytes: AtomicUsize::new(0),
+            large_alloc: AtomicUsize::new(0),
+            large_bytes: AtomicUsize::new(0),
+            unsafe_small_alloc: AtomicUsize::new(0),
+            unsafe_small_bytes: AtomicUsize::new(0),
+            unsafe_large_alloc: AtomicUsize::new(0),
+            unsafe_large_bytes: AtomicUsize::new(0),
+            event_counter: AtomicUsize::new(0),
+            timeline: Mutex::new(VecDeque::new()),
+            alloc_sites: Mutex::new(BTreeMap::new()),
+            site_unsafe_bytes: Mutex::new(BTreeMap::new()),
         }
     }
 
@@ -102,18 +341,65 @@ impl HeapTracker {
     }
 
     /// Remove an object entry from the object map and also from the unsafe
-    /// object set if the object is unsafe.
-    fn remove_obj(&self, ptr: *mut u8) {
+    /// object set if the object is unsafe. Returns whether the address was
+    /// actually present in live_objs, so callers can flag a double-free or a
+    /// free of an address this tracker never saw allocated.
+    fn remove_obj(&self, ptr: *mut u8) -> bool {
         SKIP_TRACKING.with(|flag| {
-            if flag.get() { return; }
+            if flag.get() { return true; }
 
             flag.set(true);
-            self.live_objs.lock().unwrap().remove(&(ptr as usize));
+            let was_live = self.live_objs.lock().unwrap().remove(&(ptr as usize)).is_some();
             self.live_unsafe_objs.lock().unwrap().remove(&(ptr as usize));
+            if backtrace_enabled() {
+                self.alloc_sites.lock().unwrap().remove(&(ptr as usize));
+            }
+            flag.set(false);
+            was_live
+        })
+    }
+
+    /// If HEAP_BACKTRACE is enabled, capture the current call stack and
+    /// record it as `ptr`'s allocation site. Guards the capture itself with
+    /// SKIP_TRACKING (not just the map insert), since resolving a backtrace
+    /// can itself allocate and would otherwise recurse back into this
+    /// function through `alloc`.
+    fn maybe_record_alloc_site(&self, ptr: *mut u8) {
+        if !backtrace_enabled() {
+            return;
+        }
+
+        SKIP_TRACKING.with(|flag| {
+            if flag.get() { return; }
+            flag.set(true);
+            let site = capture_site();
+            self.alloc_sites.lock().unwrap().insert(ptr as usize, site);
             flag.set(false);
         });
     }
 
+    /// The first time an unsafe object at `base_addr` is seen, attribute its
+    /// `size` bytes to whatever allocation site `maybe_record_alloc_site`
+    /// captured for it (or "<unknown allocation site>" if none was captured,
+    /// e.g. the object predates tracking), so `dump_stats` can rank call
+    /// sites by unsafe bytes instead of only reporting an aggregate total.
+    /// Does nothing when HEAP_BACKTRACE is off.
+    fn attribute_unsafe_site(&self, base_addr: usize, size: usize) {
+        if !backtrace_enabled() {
+            return;
+        }
+
+        let site = self.alloc_sites.lock().unwrap()
+            .get(&base_addr)
+            .cloned()
+            .unwrap_or_else(|| "<unknown allocation site>".to_string());
+
+        let mut sites = self.site_unsafe_bytes.lock().unwrap();
+        let entry = sites.entry(site).or_insert((0, 0));
+        entry.0 += size;
+        entry.1 += 1;
+    }
+
     // A helper method to find if a heap object based on a given pointer.
     // If found, return the information about the object.
     fn find_heap_obj(&self, ptr: *const u8) -> Option<(usize, usize)> {
@@ -136,7 +422,7 @@ impl HeapTracker {
     /// Check whether a memory access is to a heap object.
     fn access_heap_obj(&self, ptr: *const u8) {
         if self.find_heap_obj(ptr).is_some() {
-            self.total_mem_insts.fetch_add(1, Ordering::Relaxed);
+            current_shard().total_mem_insts.fetch_add(1, Ordering::Relaxed);
         }
     }
 
@@ -154,30 +440,147 @@ impl HeapTracker {
                 if self.live_unsafe_objs.lock().unwrap().insert(base_addr) {
                     // First time accessing this unsafe object.
                     self.unsafe_mem.fetch_add(size, Ordering::Relaxed);
+                    Self::update_peak(&self.peak_unsafe_mem, self.unsafe_mem.load(Ordering::Relaxed));
                     self.total_unsafe_objs.fetch_add(1, Ordering::Relaxed);
                     self.classify_obj_by_size(size, true);
+                    self.attribute_unsafe_site(base_addr, size);
                 }
-                if is_load { self.unsafe_load.fetch_add(1, Ordering::Relaxed); }
-                else { self.unsafe_store.fetch_add(1, Ordering::Relaxed); }
+                if is_load { current_shard().unsafe_load.fetch_add(1, Ordering::Relaxed); }
+                else { current_shard().unsafe_store.fetch_add(1, Ordering::Relaxed); }
 
                 flag.set(false);
             });
         }
     }
 
+    /// Fold one shard's counters into the global totals, zeroing the shard
+    /// as we go so repeated folds (dump + per-thread flush) never double-count.
+    fn fold_shard_into_totals(&self, shard: &Shard) {
+        self.total_mem_insts.fetch_add(shard.total_mem_insts.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.unsafe_load.fetch_add(shard.unsafe_load.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.unsafe_store.fetch_add(shard.unsafe_store.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+    }
+
+    /// Fold every claimed shard into the global totals. Called once from
+    /// `dump_stats` before the totals are read for reporting.
+    ///
+    /// Ordering invariant: this must observe every shard that was ever
+    /// claimed (`0..NEXT_SHARD_SLOT`, not just those a thread happened to
+    /// flush itself) and must run with this module's `SKIP_TRACKING` set,
+    /// since folding touches the global atomics directly and must not be
+    /// mistaken for an application-driven allocation/access by any
+    /// reentrancy guard. `dump_stats` sets `SKIP_TRACKING` for its entire
+    /// body before calling this.
+    fn fold_all_shards(&self) {
+        let claimed_so_far = NEXT_SHARD_SLOT.load(Ordering::Acquire).min(MAX_THREADS);
+        for i in 0..claimed_so_far {
+            self.fold_shard_into_totals(&SHARDS[i]);
+        }
+    }
+
+    /// Bump `peak` up to `candidate` with a relaxed CAS loop, racing safely
+    /// against concurrent bumps from other threads (the loser just retries
+    /// against whatever value won).
+    fn update_peak(peak: &AtomicUsize, candidate: usize) {
+        let mut current = peak.load(Ordering::Relaxed);
+        while candidate > current {
+            match peak.compare_exchange_weak(current, candidate, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
     /// Classify each heap allocation by its size.
     /// See the comment for OBJ_SIZE_NUM about the classification.
+    ///
+    /// Also splits the allocation into the small/large (mmap-class) buckets
+    /// at `mmap_threshold()`, since the two have very different allocator
+    /// cost profiles and the power-of-two histogram alone doesn't surface
+    /// whether unsafe heap pressure comes from many small objects or a few
+    /// huge ones.
     fn classify_obj_by_size(&self, size: usize, is_unsafe: bool) {
         let size_histogram = if is_unsafe {&self.unsafe_size_histogram} else
                                           {&self.size_histogram };
+        let mut bucketed = false;
         for i in 0..OBJ_SIZE_NUM - 1 {
             if size <= (1 << i) * (1 << 10) {
                 size_histogram[i].fetch_add(1, Ordering::Relaxed);
-                return;
+                bucketed = true;
+                break;
             }
         }
+        if !bucketed {
+            size_histogram[OBJ_SIZE_NUM - 1].fetch_add(1, Ordering::Relaxed);
+        }
+
+        let (alloc_counter, bytes_counter) = if size >= mmap_threshold() {
+            if is_unsafe { (&self.unsafe_large_alloc, &self.unsafe_large_bytes) }
+            else { (&self.large_alloc, &self.large_bytes) }
+        } else if is_unsafe {
+            (&self.unsafe_small_alloc, &self.unsafe_small_bytes)
+        } else {
+            (&self.small_alloc, &self.small_bytes)
+        };
+        alloc_counter.fetch_add(1, Ordering::Relaxed);
+        bytes_counter.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// If HEAP_TIMELINE is enabled, bump the event counter and - every
+    /// `timeline_interval()` events - append a `(event, total_usage,
+    /// unsafe_mem, live_obj_count)` sample to the ring buffer, dropping the
+    /// oldest sample once it's full. Called after every alloc/dealloc/
+    /// realloc; guarded by SKIP_TRACKING since locking the VecDeque can
+    /// itself allocate.
+    fn maybe_sample_timeline(&self) {
+        if !timeline_enabled() {
+            return;
+        }
+
+        let event = self.event_counter.fetch_add(1, Ordering::Relaxed) + 1;
+        if event % timeline_interval() != 0 {
+            return;
+        }
+
+        SKIP_TRACKING.with(|flag| {
+            if flag.get() { return; }
+            flag.set(true);
+
+            let total_usage = self.total_usage.load(Ordering::Relaxed);
+            let unsafe_mem = self.unsafe_mem.load(Ordering::Relaxed);
+            let live_obj_count = self.live_objs.lock().unwrap().len();
+
+            let mut timeline = self.timeline.lock().unwrap();
+            if timeline.len() >= HEAP_TIMELINE_CAPACITY {
+                timeline.pop_front();
+            }
+            timeline.push_back((event, total_usage, unsafe_mem, live_obj_count));
 
-        size_histogram[OBJ_SIZE_NUM - 1].fetch_add(1, Ordering::Relaxed);
+            flag.set(false);
+        });
+    }
+
+    /// Snapshot the objects still in live_objs at the time this is called
+    /// (normally program exit, via dump_stats), tagging each with whether
+    /// it's also in live_unsafe_objs, sorted by size. Anything left here
+    /// never saw a matching dealloc - a leak.
+    fn collect_leaks(&self) -> Vec<(usize, usize, bool)> {
+        SKIP_TRACKING.with(|flag| {
+            // `dump_stats` already holds this guard set for its whole body;
+            // restore whatever was there before rather than hardcoding
+            // `false`, so this doesn't clear it out from under the rest of
+            // that dump.
+            let prev = flag.replace(true);
+            let live_objs = self.live_objs.lock().unwrap();
+            let live_unsafe_objs = self.live_unsafe_objs.lock().unwrap();
+            let mut leaks: Vec<(usize, usize, bool)> = live_objs.iter()
+                .map(|(&addr, &size)| (addr, size, live_unsafe_objs.contains(&addr)))
+                .collect();
+            flag.set(prev);
+
+            leaks.sort_by_key(|&(_, size, _)| size);
+            leaks
+        })
     }
 
     /// Convert an array of object size hisotgram to a string.
@@ -189,11 +592,25 @@ impl HeapTracker {
 
     /// Print out heap usage stats.
     pub fn dump_stats(&self) {
+        // Everything below can itself allocate (the `format!` calls, the
+        // leak/timeline/site reports' `Vec`/`String` building, backtrace
+        // resolution, ...). None of that is an application allocation, so
+        // guard the whole dump with SKIP_TRACKING - otherwise a report's own
+        // buffers (e.g. `output` below) get inserted into `live_objs` and
+        // `dump_leak_report`, called later in this same function, finds
+        // them still live and reports them as leaked.
+        let was_tracking = SKIP_TRACKING.with(|flag| flag.replace(true));
+
+        // Fold every thread's shard into the global totals before reporting.
+        self.fold_all_shards();
+
         let heap_usage = self.total_usage.load(Ordering::Relaxed);
+        let peak_usage = self.peak_usage.load(Ordering::Relaxed);
         let heap_alloc = self.total_alloc.load(Ordering::Relaxed);
         let heap_realloc = self.total_realloc.load(Ordering::Relaxed);
         let heap_dealloc = self.total_dealloc.load(Ordering::Relaxed);
         let unsafe_mem = self.unsafe_mem.load(Ordering::Relaxed);
+        let peak_unsafe_mem = self.peak_unsafe_mem.load(Ordering::Relaxed);
         let unsafe_objs = self.total_unsafe_objs.load(Ordering::Relaxed);
         let total_mem_insts = self.total_mem_insts.load(Ordering::Relaxed);
         let unsafe_load = self.unsafe_load.load(Ordering::Relaxed);
@@ -215,23 +632,49 @@ impl HeapTracker {
         });
         let unsafe_size_histo = Self::size_hisogram_to_str(&self.unsafe_size_histogram);
 
+        let small_alloc = self.small_alloc.load(Ordering::Relaxed);
+        let small_bytes = self.small_bytes.load(Ordering::Relaxed);
+        let large_alloc = self.large_alloc.load(Ordering::Relaxed);
+        let large_bytes = self.large_bytes.load(Ordering::Relaxed);
+        let unsafe_small_alloc = self.unsafe_small_alloc.load(Ordering::Relaxed);
+        let unsafe_small_bytes = self.unsafe_small_bytes.load(Ordering::Relaxed);
+        let unsafe_large_alloc = self.unsafe_large_alloc.load(Ordering::Relaxed);
+        let unsafe_large_bytes = self.unsafe_large_bytes.load(Ordering::Relaxed);
+
+        if structured_output_enabled() {
+            let _ = write_record(&self.stats_as_json(
+                heap_usage, peak_usage, heap_alloc, heap_realloc, heap_dealloc, unsafe_mem, peak_unsafe_mem,
+                unsafe_objs, total_mem_insts, unsafe_load, unsafe_store,
+                small_alloc, small_bytes, large_alloc, large_bytes,
+                unsafe_small_alloc, unsafe_small_bytes, unsafe_large_alloc, unsafe_large_bytes,
+            ), "heap_stat.json");
+            SKIP_TRACKING.with(|flag| flag.set(was_tracking));
+            return;
+        }
+
         let output = format!(
             concat!(
                 "\n===== Heap Usage Statistics =====\n",
                 "Total heap usage: {} bytes\n",
+                "Peak heap usage: {} bytes\n",
                 "Total heap allocations: {}\n",
                 "Total heap re-allocations: {}\n",
                 "Total heap deallocations: {}\n",
                 "Unsafe heap memory: {}\n",
+                "Peak unsafe heap memory: {}\n",
                 "Unsafe heap objects: {}\n",
                 "Unsafe memory instructions: {}\n",
                 "Unsafe load: {}\n",
                 "Unsafe store: {}\n",
                 "Size histogram: {}\n",
                 "Unsafe size histogram: {}\n",
+                "Small/large allocations (< {} bytes / >=): {} / {} ({} / {} bytes)\n",
+                "Unsafe small/large allocations: {} / {} ({} / {} bytes)\n",
             ),
-            heap_usage, heap_alloc, heap_realloc, heap_dealloc, unsafe_mem, unsafe_objs,
-            total_mem_insts, unsafe_load, unsafe_store, size_histo, unsafe_size_histo
+            heap_usage, peak_usage, heap_alloc, heap_realloc, heap_dealloc, unsafe_mem, peak_unsafe_mem,
+            unsafe_objs, total_mem_insts, unsafe_load, unsafe_store, size_histo, unsafe_size_histo,
+            mmap_threshold(), small_alloc, large_alloc, small_bytes, large_bytes,
+            unsafe_small_alloc, unsafe_large_alloc, unsafe_small_bytes, unsafe_large_bytes,
         );
 
         // Write the output to a tmp file.
@@ -245,6 +688,161 @@ impl HeapTracker {
         if cfg!(debug_assertions) {
             dbg!("{}", &output);
         }
+
+        self.dump_leak_report();
+        self.dump_timeline();
+        self.dump_site_report();
+
+        SKIP_TRACKING.with(|flag| flag.set(was_tracking));
+    }
+
+    /// Build the structured (`UNSAFE_BENCH_OUTPUT_FORMAT=json`) rendering of
+    /// heap stats: the same totals as the plaintext report, plus leak,
+    /// timeline and allocation-site data nested under their own keys (rather
+    /// than separate files) so a single `heap_stat.json` is a complete,
+    /// diffable snapshot. Allocation-site backtraces - the one part of this
+    /// report that varies machine-to-machine (absolute paths, line numbers)
+    /// - are kept under their own `"frames"` key so consumers can normalize
+    /// or drop them without touching the numeric fields.
+    fn stats_as_json(
+        &self,
+        heap_usage: usize, peak_usage: usize, heap_alloc: usize, heap_realloc: usize, heap_dealloc: usize,
+        unsafe_mem: usize, peak_unsafe_mem: usize, unsafe_objs: usize, total_mem_insts: usize,
+        unsafe_load: usize, unsafe_store: usize,
+        small_alloc: usize, small_bytes: usize, large_alloc: usize, large_bytes: usize,
+        unsafe_small_alloc: usize, unsafe_small_bytes: usize, unsafe_large_alloc: usize, unsafe_large_bytes: usize,
+    ) -> JsonValue {
+        let leaks = self.collect_leaks();
+        let leaked_bytes: u64 = leaks.iter().map(|&(_, size, _)| size as u64).sum();
+        let leaked_unsafe_objs = leaks.iter().filter(|&&(_, _, is_unsafe)| is_unsafe).count();
+
+        let leak_entries = leaks.iter().map(|&(addr, size, is_unsafe)| JsonValue::Object(vec![
+            ("address", JsonValue::Str(format!("0x{:x}", addr))),
+            ("size", JsonValue::UInt(size as u64)),
+            ("unsafe", JsonValue::Bool(is_unsafe)),
+        ])).collect();
+
+        let timeline_entries = if timeline_enabled() {
+            self.timeline.lock().unwrap().iter().map(|&(event, total_usage, unsafe_mem, live_objs)| JsonValue::Object(vec![
+                ("event", JsonValue::UInt(event as u64)),
+                ("total_usage", JsonValue::UInt(total_usage as u64)),
+                ("unsafe_mem", JsonValue::UInt(unsafe_mem as u64)),
+                ("live_objects", JsonValue::UInt(live_objs as u64)),
+            ])).collect()
+        } else {
+            Vec::new()
+        };
+
+        let site_entries = if backtrace_enabled() {
+            let sites = self.site_unsafe_bytes.lock().unwrap();
+            let mut ranked: Vec<(&String, &(usize, usize))> = sites.iter().collect();
+            ranked.sort_by(|a, b| b.1.0.cmp(&a.1.0));
+            ranked.iter().map(|&(site, &(bytes, objs))| JsonValue::Object(vec![
+                ("bytes", JsonValue::UInt(bytes as u64)),
+                ("objects", JsonValue::UInt(objs as u64)),
+                ("frames", JsonValue::Array(site.lines().map(|l| JsonValue::Str(l.to_string())).collect())),
+            ])).collect()
+        } else {
+            Vec::new()
+        };
+
+        JsonValue::Object(vec![
+            ("kind", JsonValue::Str("heap".to_string())),
+            ("total_accesses", JsonValue::UInt(total_mem_insts as u64)),
+            ("unsafe_accesses", JsonValue::UInt((unsafe_load + unsafe_store) as u64)),
+            ("unsafe_loads", JsonValue::UInt(unsafe_load as u64)),
+            ("unsafe_stores", JsonValue::UInt(unsafe_store as u64)),
+            ("total_usage_bytes", JsonValue::UInt(heap_usage as u64)),
+            ("peak_usage_bytes", JsonValue::UInt(peak_usage as u64)),
+            ("total_allocations", JsonValue::UInt(heap_alloc as u64)),
+            ("total_reallocations", JsonValue::UInt(heap_realloc as u64)),
+            ("total_deallocations", JsonValue::UInt(heap_dealloc as u64)),
+            ("unsafe_mem_bytes", JsonValue::UInt(unsafe_mem as u64)),
+            ("peak_unsafe_mem_bytes", JsonValue::UInt(peak_unsafe_mem as u64)),
+            ("unsafe_objects", JsonValue::UInt(unsafe_objs as u64)),
+            ("small_allocations", JsonValue::UInt(small_alloc as u64)),
+            ("small_bytes", JsonValue::UInt(small_bytes as u64)),
+            ("large_allocations", JsonValue::UInt(large_alloc as u64)),
+            ("large_bytes", JsonValue::UInt(large_bytes as u64)),
+            ("unsafe_small_allocations", JsonValue::UInt(unsafe_small_alloc as u64)),
+            ("unsafe_small_bytes", JsonValue::UInt(unsafe_small_bytes as u64)),
+            ("unsafe_large_allocations", JsonValue::UInt(unsafe_large_alloc as u64)),
+            ("unsafe_large_bytes", JsonValue::UInt(unsafe_large_bytes as u64)),
+            ("invalid_frees", JsonValue::UInt(self.invalid_frees.load(Ordering::Relaxed) as u64)),
+            ("leaked_objects", JsonValue::UInt(leaks.len() as u64)),
+            ("leaked_bytes", JsonValue::UInt(leaked_bytes)),
+            ("leaked_unsafe_objects", JsonValue::UInt(leaked_unsafe_objs as u64)),
+            ("leaks", JsonValue::Array(leak_entries)),
+            ("timeline", JsonValue::Array(timeline_entries)),
+            ("allocation_sites", JsonValue::Array(site_entries)),
+        ])
+    }
+
+    /// Report the allocation call sites responsible for unsafe heap bytes,
+    /// ranked by bytes, when HEAP_BACKTRACE was enabled. Brings heaptrack's
+    /// per-allocation stack-trace attribution into this crate's aggregate
+    /// output: "N bytes of unsafe heap" becomes a list of which code
+    /// allocated it.
+    fn dump_site_report(&self) {
+        if !backtrace_enabled() {
+            return;
+        }
+
+        let sites = self.site_unsafe_bytes.lock().unwrap();
+        let mut ranked: Vec<(&String, &(usize, usize))> = sites.iter().collect();
+        ranked.sort_by(|a, b| b.1.0.cmp(&a.1.0));
+
+        let mut output = String::from("\n===== Unsafe Allocation Call Sites =====\n");
+        for (rank, (site, &(bytes, objs))) in ranked.iter().enumerate() {
+            output.push_str(&format!("#{} bytes={} objects={}\n{}\n\n", rank + 1, bytes, objs, site));
+        }
+
+        let _ = write_output(&output, "heap_sites.stat");
+    }
+
+    /// Flush the HEAP_TIMELINE ring buffer (if the mode was enabled) as a
+    /// CSV profile so the memory curve over the run - not just its final
+    /// snapshot - can be plotted, mirroring glibc memusage's logging model.
+    fn dump_timeline(&self) {
+        if !timeline_enabled() {
+            return;
+        }
+
+        let mut output = String::from("event,total_usage,unsafe_mem,live_objects\n");
+        for &(event, total_usage, unsafe_mem, live_objs) in self.timeline.lock().unwrap().iter() {
+            output.push_str(&format!("{},{},{},{}\n", event, total_usage, unsafe_mem, live_objs));
+        }
+
+        let _ = write_output(&output, "heap_timeline.stat");
+    }
+
+    /// Report everything still in live_objs at exit (never matched by a
+    /// dealloc - a leak) and how many dealloc/realloc calls targeted an
+    /// address this tracker never saw allocated (a double-free, or a free of
+    /// untracked memory). Imports the num_allocs/num_frees leak-and-double-
+    /// free accounting idea from mockalloc into this allocator.
+    fn dump_leak_report(&self) {
+        let invalid_frees = self.invalid_frees.load(Ordering::Relaxed);
+        let leaks = self.collect_leaks();
+        let leaked_bytes: usize = leaks.iter().map(|&(_, size, _)| size).sum();
+        let leaked_unsafe_objs = leaks.iter().filter(|&&(_, _, is_unsafe)| is_unsafe).count();
+
+        let mut output = format!(
+            concat!(
+                "\n===== Heap Leak Report =====\n",
+                "Leaked objects: {}\n",
+                "Leaked bytes: {}\n",
+                "Leaked unsafe objects: {}\n",
+                "Invalid frees (double-free or free of untracked memory): {}\n",
+            ),
+            leaks.len(), leaked_bytes, leaked_unsafe_objs, invalid_frees,
+        );
+
+        for (addr, size, is_unsafe) in &leaks {
+            output.push_str(&format!("  addr=0x{:x} size={} unsafe={}\n", addr, size, is_unsafe));
+        }
+
+        let _ = write_output(&output, "heap_leaks.stat");
     }
 }
 
@@ -256,9 +854,12 @@ unsafe impl GlobalAlloc for HeapTracker {
         // Add this new object's range to the map.
         if !ptr.is_null() && !SKIP_TRACKING.with(|flag| flag.get()) {
             self.total_usage.fetch_add(size, Ordering::Relaxed);
+            Self::update_peak(&self.peak_usage, self.total_usage.load(Ordering::Relaxed));
             self.total_alloc.fetch_add(1, Ordering::Relaxed);
             self.classify_obj_by_size(size, false);
             self.insert_obj(ptr, layout.size());
+            self.maybe_record_alloc_site(ptr);
+            self.maybe_sample_timeline();
         }
 
         ptr
@@ -267,7 +868,10 @@ unsafe impl GlobalAlloc for HeapTracker {
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         if !SKIP_TRACKING.with(|flag| flag.get()) {
             self.total_dealloc.fetch_add(1, Ordering::Relaxed);
-            self.remove_obj(ptr);
+            if !self.remove_obj(ptr) {
+                self.invalid_frees.fetch_add(1, Ordering::Relaxed);
+            }
+            self.maybe_sample_timeline();
         }
 
         System.dealloc(ptr, layout)
@@ -278,9 +882,12 @@ unsafe impl GlobalAlloc for HeapTracker {
 
         if !ptr.is_null() && !SKIP_TRACKING.with(|flag| flag.get()) {
             self.total_usage.fetch_add(layout.size(), Ordering::Relaxed);
+            Self::update_peak(&self.peak_usage, self.total_usage.load(Ordering::Relaxed));
             self.total_alloc.fetch_add(1, Ordering::Relaxed);
             self.classify_obj_by_size(layout.size(), false);
             self.insert_obj(ptr, layout.size());
+            self.maybe_record_alloc_site(ptr);
+            self.maybe_sample_timeline();
         }
 
         ptr
@@ -294,16 +901,21 @@ unsafe impl GlobalAlloc for HeapTracker {
             if new_ptr != ptr {
                 // Reallocating to a new address. Remove the old entry and record
                 // the new entry.
-                self.remove_obj(ptr);
+                if !self.remove_obj(ptr) {
+                    self.invalid_frees.fetch_add(1, Ordering::Relaxed);
+                }
                 self.total_realloc.fetch_add(1, Ordering::Relaxed);
                 self.classify_obj_by_size(new_size, false);
             }
             self.insert_obj(new_ptr, new_size);
+            self.maybe_record_alloc_site(new_ptr);
+            self.maybe_sample_timeline();
         }
 
         // Update total heap usage if new_size differs than the old size.
         if new_size > layout.size() {
             self.total_usage.fetch_add(new_size - layout.size(), Ordering::Relaxed);
+            Self::update_peak(&self.peak_usage, self.total_usage.load(Ordering::Relaxed));
         } else {
             self.total_usage.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
         }
@@ -334,8 +946,73 @@ pub extern "C" fn dyn_mem_access(ptr: *const u8) {
     HEAP_TRACKER.access_heap_obj(ptr);
 }
 
-/// Dump heap usage stats at program termination time
-#[ctor::dtor]
+/// Fold the calling thread's shard into the global totals now and release
+/// its slot for reuse by a later thread. A shard that is never flushed keeps
+/// its counts and is folded by `fold_all_shards` at shutdown.
+#[no_mangle]
+pub extern "C" fn __heap_flush_thread() {
+    THREAD_SHARD_SLOT.with(|slot_cell| {
+        if let Some(i) = slot_cell.get() {
+            HEAP_TRACKER.fold_shard_into_totals(&SHARDS[i]);
+            SHARDS[i].claimed.store(false, Ordering::Release);
+            slot_cell.set(None);
+        }
+    });
+}
+
+/// Dump heap usage stats at program termination time.
 fn dump_stats() {
     HEAP_TRACKER.dump_stats();
+}
+
+/// Register `dump_stats` with the crate's unified shutdown coordinator
+/// instead of installing our own `#[ctor::dtor]`, so heap stats flush in a
+/// single, ordered place alongside every other feature module.
+#[ctor::ctor]
+fn register_heap_tracker_shutdown() {
+    crate::register_at_exit(dump_stats);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_leaks_reports_unmatched_allocations() {
+        let tracker = HeapTracker::new();
+
+        // Two live allocations, one of them also touched as "unsafe".
+        let leaked = 0x1000 as *mut u8;
+        let unsafe_leaked = 0x2000 as *mut u8;
+        tracker.insert_obj(leaked, 16);
+        tracker.insert_obj(unsafe_leaked, 32);
+        tracker.live_unsafe_objs.lock().unwrap().insert(unsafe_leaked as usize);
+
+        // A third allocation that is freed before the leak snapshot is taken
+        // shouldn't show up as a leak.
+        let freed = 0x3000 as *mut u8;
+        tracker.insert_obj(freed, 8);
+        assert!(tracker.remove_obj(freed));
+
+        let leaks = tracker.collect_leaks();
+        assert_eq!(leaks.len(), 2);
+        assert!(leaks.contains(&(leaked as usize, 16, false)));
+        assert!(leaks.contains(&(unsafe_leaked as usize, 32, true)));
+    }
+
+    #[test]
+    fn test_remove_obj_detects_invalid_free() {
+        let tracker = HeapTracker::new();
+
+        let ptr = 0x4000 as *mut u8;
+        tracker.insert_obj(ptr, 16);
+
+        // First free matches a known allocation.
+        assert!(tracker.remove_obj(ptr));
+
+        // Freeing it again (or any address this tracker never saw
+        // allocated) is a double-free / invalid free: remove_obj reports
+        // it wasn't live so GlobalAlloc::dealloc can bump invalid_frees.
+        assert!(!tracker.remove_obj(ptr));
+    }
 }
\ No newline at end of file
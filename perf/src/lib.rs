@@ -15,7 +15,7 @@
 //! ## Features
 //! 
 //! - `heap_tracker`: Enables heap memory access tracking
-//! - `cpu_cycle_counter`: Enables CPU cycle counting (x86_64 only)
+//! - `cpu_cycle_counter`: Enables CPU cycle counting (x86_64 and aarch64)
 //! - `unsafe_coverage`: Enables unsafe code line coverage tracking
 //! - `unsafe_counter`: Enables unsafe instruction counting and function statistics
 //! 
@@ -27,15 +27,31 @@
 //! For `unsafe_counter`, two LLVM passes work together:
 //! - `UnsafeFunctionTrackerPass` (module pass): Tracks function calls and metadata
 //! - `UnsafeInstCounterPass` (function pass): Counts unsafe instructions
+//!
+//! With `--no-default-features --features unsafe_counter,no_std`, this crate
+//! builds `#![no_std]` for bare-metal/RTOS targets: `unsafe_counter` has its
+//! own `no_std` sink (see its module docs), and everything here that needs
+//! `std` - the JSON writer, the file-based output sink, the at-exit hook
+//! registry's `Mutex<Vec<fn()>>` - is gated behind `not(feature = "no_std")`
+//! and replaced with a fixed-capacity, allocator-free equivalent where the
+//! other modules still depend on it (`register_at_exit`).
+
+#![cfg_attr(feature = "no_std", no_std)]
 
+#[cfg(not(feature = "no_std"))]
 use std::fs::OpenOptions;
+#[cfg(not(feature = "no_std"))]
 use std::io::{Result as IoResult, Write};
-use std::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(not(feature = "no_std"))]
+use std::sync::Mutex;
+#[cfg(not(feature = "no_std"))]
 use std::cell::Cell;
 
 /// Global flag to ensure initialization only happens once across all modules
 static RUNTIME_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
+#[cfg(not(feature = "no_std"))]
 thread_local! {
     /// Thread-local flag for preventing recursive tracking during internal operations.
     /// This is shared across all modules to ensure consistent behavior.
@@ -47,7 +63,9 @@ thread_local! {
 // ================================================================================================
 
 // Re-export PathBuf for convenience if needed, but we'll use it internally
+#[cfg(not(feature = "no_std"))]
 use std::path::{PathBuf};
+#[cfg(not(feature = "no_std"))]
 use std::env;
 
 // ... imports ...
@@ -57,8 +75,9 @@ use std::env;
 // ================================================================================================
 
 /// Get the directory where output files should be written.
-/// 
+///
 /// Defaults to "UNSAFE_BENCH_OUTPUT_DIR" environment variable, or "/tmp" if not set.
+#[cfg(not(feature = "no_std"))]
 pub fn get_output_dir() -> PathBuf {
     match env::var("UNSAFE_BENCH_OUTPUT_DIR") {
         Ok(val) => PathBuf::from(val),
@@ -78,6 +97,7 @@ pub fn get_output_dir() -> PathBuf {
 /// # Returns
 /// * `Ok(())` if the write was successful
 /// * `Err(io::Error)` if there was an I/O error
+#[cfg(not(feature = "no_std"))]
 pub fn write_output(content: &str, filename: &str) -> IoResult<()> {
     // Use GLOBAL_SKIP_TRACKING to prevent any allocations during file I/O
     // from being tracked by our monitoring systems
@@ -123,7 +143,7 @@ pub fn initialize_runtime() {
     }
     
     // Perform any global initialization needed across all modules
-    #[cfg(any(feature = "heap_tracker", feature = "cpu_cycle_counter", feature = "unsafe_coverage", feature = "unsafe_counter"))]
+    #[cfg(all(any(feature = "heap_tracker", feature = "cpu_cycle_counter", feature = "unsafe_coverage", feature = "unsafe_counter"), not(feature = "no_std")))]
     {
         // Initialize thread-local tracking state
         GLOBAL_SKIP_TRACKING.with(|flag| flag.set(false));
@@ -134,12 +154,122 @@ pub fn initialize_runtime() {
 }
 
 /// Check if the runtime monitoring system has been initialized.
-/// 
+///
 /// This function can be used to verify that the runtime is properly set up.
 pub fn is_runtime_initialized() -> bool {
     RUNTIME_INITIALIZED.load(Ordering::Acquire)
 }
 
+// ================================================================================================
+// STRUCTURED (JSON) OUTPUT
+// ================================================================================================
+
+/// Minimal JSON value type for this runtime's hand-rolled structured output
+/// mode (`UNSAFE_BENCH_OUTPUT_FORMAT=json`), shared by every monitor module
+/// so cross-module tooling only needs one parser/schema convention instead
+/// of each module inventing its own. Hand-rolled (no serde) to keep this
+/// crate's dependency footprint small, matching the other ad hoc JSON
+/// writers already in this runtime.
+///
+/// `Object` fields are emitted in the order given, so build them already
+/// key-sorted when a stable, diffable schema matters.
+///
+/// Not available under `no_std` (needs `String`/`Vec` and the file-based
+/// sink below); `unsafe_counter` has its own allocator-free `no_std` dump
+/// path instead.
+#[cfg(not(feature = "no_std"))]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(&'static str, JsonValue)>),
+}
+
+#[cfg(not(feature = "no_std"))]
+impl JsonValue {
+    fn write_to(&self, out: &mut String) {
+        match self {
+            JsonValue::Null => out.push_str("null"),
+            JsonValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            JsonValue::UInt(n) => out.push_str(&n.to_string()),
+            JsonValue::Int(n) => out.push_str(&n.to_string()),
+            JsonValue::Float(f) => out.push_str(&f.to_string()),
+            JsonValue::Str(s) => {
+                out.push('"');
+                for c in s.chars() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+            }
+            JsonValue::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    item.write_to(out);
+                }
+                out.push(']');
+            }
+            JsonValue::Object(fields) => {
+                out.push('{');
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push('"');
+                    out.push_str(key);
+                    out.push_str("\":");
+                    value.write_to(out);
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    /// Serialize to a compact JSON string (no pretty-printing).
+    pub fn to_json_string(&self) -> String {
+        let mut out = String::new();
+        self.write_to(&mut out);
+        out
+    }
+}
+
+/// Whether structured JSON output is selected for this process, via
+/// `UNSAFE_BENCH_OUTPUT_FORMAT=json` alongside the existing
+/// `UNSAFE_BENCH_OUTPUT_DIR` variable. Checked fresh each call (env lookups
+/// are cheap and this only runs at dump time, not in any hot path).
+#[cfg(not(feature = "no_std"))]
+pub fn structured_output_enabled() -> bool {
+    matches!(env::var("UNSAFE_BENCH_OUTPUT_FORMAT").ok().as_deref(), Some("json"))
+}
+
+/// Serialize `value` and append it to `filename` in the output directory,
+/// through the same `GLOBAL_SKIP_TRACKING`-guarded path as `write_output` so
+/// the serialization itself is never attributed back to the tracked program.
+#[cfg(not(feature = "no_std"))]
+pub fn write_record(value: &JsonValue, filename: &str) -> IoResult<()> {
+    let json = GLOBAL_SKIP_TRACKING.with(|flag| {
+        let was_tracking = flag.replace(true);
+        let json = value.to_json_string();
+        flag.set(was_tracking);
+        json
+    });
+    write_output(&json, filename)
+}
+
 // ================================================================================================
 // FEATURE-CONDITIONAL MODULE IMPORTS
 // ================================================================================================
@@ -147,7 +277,7 @@ pub fn is_runtime_initialized() -> bool {
 #[cfg(feature = "heap_tracker")]
 pub mod heap_tracker;
 
-#[cfg(all(target_arch = "x86_64", feature = "cpu_cycle_counter"))]
+#[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), feature = "cpu_cycle_counter"))]
 pub mod cpu_cycle_counter;
 
 #[cfg(feature = "unsafe_coverage")] 
@@ -163,12 +293,17 @@ pub mod unsafe_counter;
 // Re-export the main runtime functions that LLVM passes expect to find
 #[cfg(feature = "heap_tracker")]
 pub use heap_tracker::{
-    dyn_mem_access, 
+    dyn_mem_access,
     dyn_unsafe_mem_access,
-    // Note: heap_tracker dump_stats is called automatically via dtor
+    // Note: heap_tracker dump_stats is registered with the unified shutdown hook
 };
 
-#[cfg(all(target_arch = "x86_64", feature = "cpu_cycle_counter"))]
+// Folds a thread's per-thread shard into the global totals before it exits;
+// mirrors `unsafe_counter::__unsafe_flush_thread`.
+#[cfg(feature = "heap_tracker")]
+pub use heap_tracker::__heap_flush_thread;
+
+#[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), feature = "cpu_cycle_counter"))]
 pub use cpu_cycle_counter::{
     // Core functions called by LLVM instrumentation
     record_program_start,           // Called from module constructor
@@ -189,12 +324,18 @@ pub use unsafe_coverage::{
     register_unsafe_line,           // Called from module constructor: (line, file)
     track_unsafe_line_execution,    // Called at runtime: (line, file)
     print_unsafe_coverage_stats,    // Called from module destructor
-    
+
     // Additional utility functions for programmatic access
     get_unsafe_coverage_percentage,
     get_registered_unsafe_lines_count,
     get_executed_unsafe_lines_count,
     reset_unsafe_coverage_stats,
+
+    // Alternate report format for tooling integration (genhtml and friends)
+    write_unsafe_coverage_lcov,
+
+    // Toggle cumulative (cross-run) coverage aggregation
+    set_unsafe_coverage_merge,
 };
 
 #[cfg(feature = "unsafe_counter")]
@@ -205,19 +346,37 @@ pub use unsafe_counter::{
     __unsafe_init_metadata,         // Initialize metadata table from compile-time data
     __unsafe_record_function,       // Record function call at entry
     
-    // Called by UnsafeInstCounterPass (function pass)  
+    // Called by UnsafeInstCounterPass (function pass)
     __unsafe_record_block,          // Record basic block statistics
-    
+    __unsafe_record_block2,         // Record basic block statistics, with the semantic unsafe-op taxonomy
+
     // Called at program termination
     __unsafe_dump_stats,            // Dump final statistics
 };
 
+// Folds a thread's per-thread shard into the global totals before it exits;
+// only present when the per-thread sharded counters are compiled in.
+#[cfg(all(feature = "unsafe_counter", not(feature = "no_std")))]
+pub use unsafe_counter::__unsafe_flush_thread;
+
+// Output format/sink selection and the memfd sink's fd getter; only present
+// when the per-thread sharded counters (and thus the std-only sink
+// machinery) are compiled in.
+#[cfg(all(feature = "unsafe_counter", not(feature = "no_std")))]
+pub use unsafe_counter::{__unsafe_get_memfd, __unsafe_set_output_format};
+
+// Labeled phase/interval snapshot, for long-running processes that never
+// hit the final `#[dtor]`-driven dump; only present alongside the sharded
+// counters.
+#[cfg(all(feature = "unsafe_counter", not(feature = "no_std")))]
+pub use unsafe_counter::__unsafe_snapshot_stats;
+
 // ================================================================================================
 // RUNTIME INITIALIZATION
 // ================================================================================================
 
 /// Automatic runtime initialization using ctor.
-/// 
+///
 /// This ensures the runtime is initialized before any instrumented code runs,
 /// regardless of which features are enabled.
 #[ctor::ctor]
@@ -225,6 +384,106 @@ fn init_unsafe_perf_runtime() {
     initialize_runtime();
 }
 
+// ================================================================================================
+// UNIFIED SHUTDOWN
+// ================================================================================================
+
+/// Functions to run at process shutdown, most-recently-registered first.
+/// Each feature module (`heap_tracker`, `cpu_cycle_counter`,
+/// `unsafe_coverage`, `unsafe_counter`) registers its own dump/flush
+/// function here from its own `#[ctor::ctor]`, instead of installing its own
+/// `#[ctor::dtor]`. This gives a single, ordered, single-shot shutdown
+/// regardless of which combination of features is linked in, mirroring how
+/// the standard runtime centralizes `at_exit` cleanup.
+#[cfg(not(feature = "no_std"))]
+static AT_EXIT_HOOKS: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
+
+/// `no_std` counterpart of `AT_EXIT_HOOKS`: no allocator is assumed, so this
+/// is a fixed-capacity table of hook pointers (as `usize`, `0` meaning empty)
+/// claimed with a single compare-exchange per slot - the same lock-free,
+/// fixed-capacity style `unsafe_counter`'s own `no_std` path already uses.
+#[cfg(feature = "no_std")]
+const MAX_AT_EXIT_HOOKS: usize = 8;
+
+#[cfg(feature = "no_std")]
+static AT_EXIT_HOOKS: [AtomicUsize; MAX_AT_EXIT_HOOKS] = {
+    const SLOT: AtomicUsize = AtomicUsize::new(0);
+    [SLOT; MAX_AT_EXIT_HOOKS]
+};
+
+/// Guards `run_at_exit_hooks` so the registry only drains once, even if a
+/// module's dump function is also called manually (e.g. via its own
+/// `print_*_stats`/`__unsafe_dump_stats` entry point) before process exit.
+static AT_EXIT_RAN: AtomicBool = AtomicBool::new(false);
+
+/// Register a function to run at process shutdown. Hooks run in LIFO order
+/// (most-recently-registered first), the same ordering convention as the C
+/// runtime's `atexit`.
+#[cfg(not(feature = "no_std"))]
+pub fn register_at_exit(f: fn()) {
+    AT_EXIT_HOOKS.lock().unwrap().push(f);
+}
+
+/// `no_std` counterpart of `register_at_exit`: claims the first empty slot.
+/// Drops the hook silently if every slot is already taken, rather than
+/// panicking or allocating.
+#[cfg(feature = "no_std")]
+pub fn register_at_exit(f: fn()) {
+    for slot in AT_EXIT_HOOKS.iter() {
+        if slot.compare_exchange(0, f as usize, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+            return;
+        }
+    }
+}
+
+/// Drain and run every registered shutdown hook, most-recently-registered
+/// first. Safe to call more than once; only the first call does anything.
+#[cfg(not(feature = "no_std"))]
+fn run_at_exit_hooks() {
+    if AT_EXIT_RAN.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+        return;
+    }
+
+    // Mark the rest of shutdown as runtime-internal work. The heap tracker
+    // does not read this flag: its `dump_stats` guards its own bookkeeping
+    // with its module-local `SKIP_TRACKING`.
+    GLOBAL_SKIP_TRACKING.with(|flag| flag.set(true));
+
+    let hooks: Vec<fn()> = {
+        let mut registered = AT_EXIT_HOOKS.lock().unwrap();
+        registered.drain(..).rev().collect()
+    };
+    for hook in hooks {
+        hook();
+    }
+}
+
+/// `no_std` counterpart of `run_at_exit_hooks`: slots are walked back-to-front
+/// (LIFO, mirroring `register_at_exit`'s fill order) and swapped out as
+/// they're claimed, so a hook can't run twice even if this were re-entered.
+#[cfg(feature = "no_std")]
+fn run_at_exit_hooks() {
+    if AT_EXIT_RAN.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+        return;
+    }
+
+    for slot in AT_EXIT_HOOKS.iter().rev() {
+        let ptr = slot.swap(0, Ordering::AcqRel);
+        if ptr != 0 {
+            let hook: fn() = unsafe { core::mem::transmute::<usize, fn()>(ptr) };
+            hook();
+        }
+    }
+}
+
+/// The crate's single `#[ctor::dtor]`. Every feature module plugs into this
+/// via `register_at_exit` rather than installing its own destructor, so
+/// shutdown ordering and single-shot guarantees live in one place.
+#[ctor::dtor]
+fn unified_shutdown() {
+    run_at_exit_hooks();
+}
+
 // ================================================================================================
 // TESTING UTILITIES
 // ================================================================================================
@@ -239,6 +498,7 @@ mod tests {
         assert!(is_runtime_initialized());
     }
     
+    #[cfg(not(feature = "no_std"))]
     #[test]
     fn test_global_skip_tracking() {
         // Test the global skip tracking flag
@@ -262,7 +522,7 @@ mod tests {
         }
     }
     
-#[cfg(all(target_arch = "x86_64", feature = "cpu_cycle_counter"))]
+#[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), feature = "cpu_cycle_counter"))]
 #[test]
 fn test_cpu_cycle_counter_functions() {
     // Record program start
@@ -384,6 +644,25 @@ fn test_unsafe_coverage_functions() {
         }
     }
     
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn test_json_value_escaping() {
+        let value = JsonValue::Object(vec![
+            ("name", JsonValue::Str("line\"1\\line\n\ttab\u{1}".to_string())),
+            ("count", JsonValue::UInt(3)),
+            ("ratio", JsonValue::Float(0.5)),
+            ("ok", JsonValue::Bool(true)),
+            ("missing", JsonValue::Null),
+            ("tags", JsonValue::Array(vec![JsonValue::Int(-1), JsonValue::UInt(2)])),
+        ]);
+
+        assert_eq!(
+            value.to_json_string(),
+            "{\"name\":\"line\\\"1\\\\line\\n\\ttab\\u0001\",\"count\":3,\"ratio\":0.5,\"ok\":true,\"missing\":null,\"tags\":[-1,2]}"
+        );
+    }
+
+    #[cfg(not(feature = "no_std"))]
     #[test]
     fn test_write_output() {
         // Test that write_output works without panicking
@@ -3,14 +3,32 @@
 //! Lock-free, zero-allocation runtime supporting the two-pass system:
 //! - UnsafeFunctionTrackerPass (module pass): tracks function calls
 //! - UnsafeInstCounterPass (function pass): counts unsafe instructions
+//!
+//! Instruction counters are sharded per-thread (see `Shard`/`current_shard`)
+//! so `record_block` never contends with other threads; shards are folded
+//! into the global totals at dump time or on early thread exit.
+//!
+//! Besides the one-shot final dump, a long-running process can call
+//! `__unsafe_snapshot_stats` to emit a labeled report of the counters so far
+//! and (by default) reset them, so unsafe-instruction rates can be measured
+//! per request-handling window or benchmark iteration rather than as one
+//! lifetime total. See `UnsafeTracker::snapshot`.
 
-use std::sync::atomic::{AtomicU64, AtomicU32, AtomicBool, Ordering};
-use std::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU64, AtomicU32, AtomicUsize, AtomicBool, Ordering};
+use core::mem::MaybeUninit;
+#[cfg(not(feature = "no_std"))]
+use core::cell::Cell;
+#[cfg(not(feature = "no_std"))]
 use crate::write_output;
 
 /// Maximum number of functions we can track
 const MAX_FUNCTIONS: usize = 65536;
 
+/// Maximum number of threads with their own counter shard (see `Shard`).
+/// Threads beyond this fall back to sharing slot 0.
+#[cfg(not(feature = "no_std"))]
+const MAX_THREADS: usize = 4096;
+
 /// Function metadata from compile-time analysis
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -55,6 +73,131 @@ impl AtomicBitset {
         let bit_idx = index % 64;
         (self.words[word_idx].value.load(Ordering::Relaxed) & (1u64 << bit_idx)) != 0
     }
+
+    #[cfg(not(feature = "no_std"))]
+    #[inline]
+    fn clear(&self, index: usize) {
+        let word_idx = index / 64;
+        let bit_idx = index % 64;
+        self.words[word_idx].value.fetch_and(!(1u64 << bit_idx), Ordering::Relaxed);
+    }
+}
+
+/// Per-thread counter shard for the instruction-counting hot path.
+///
+/// Each thread claims exactly one shard and only ever updates its own
+/// fields, so `record_block` never contends with other threads on the same
+/// cache line the way the old single global `AtomicU64`s did. Shards are
+/// folded into `UnsafeTracker`'s global totals at dump time and whenever a
+/// thread flushes early via `__unsafe_flush_thread`.
+#[cfg(not(feature = "no_std"))]
+#[repr(align(64))]
+struct Shard {
+    claimed: AtomicBool,
+    total_instructions: AtomicU64,
+    total_unsafe_instructions: AtomicU64,
+    unsafe_loads: AtomicU64,
+    unsafe_stores: AtomicU64,
+    unsafe_calls: AtomicU64,
+    unsafe_casts: AtomicU64,
+    unsafe_geps: AtomicU64,
+    unsafe_others: AtomicU64,
+    deref_raw_pointer: AtomicU64,
+    access_mutable_static: AtomicU64,
+    union_field_access: AtomicU64,
+    inline_asm: AtomicU64,
+    call_unsafe_fn: AtomicU64,
+    access_extern_item: AtomicU64,
+}
+
+#[cfg(not(feature = "no_std"))]
+impl Shard {
+    const fn new() -> Self {
+        Self {
+            claimed: AtomicBool::new(false),
+            total_instructions: AtomicU64::new(0),
+            total_unsafe_instructions: AtomicU64::new(0),
+            unsafe_loads: AtomicU64::new(0),
+            unsafe_stores: AtomicU64::new(0),
+            unsafe_calls: AtomicU64::new(0),
+            unsafe_casts: AtomicU64::new(0),
+            unsafe_geps: AtomicU64::new(0),
+            unsafe_others: AtomicU64::new(0),
+            deref_raw_pointer: AtomicU64::new(0),
+            access_mutable_static: AtomicU64::new(0),
+            union_field_access: AtomicU64::new(0),
+            inline_asm: AtomicU64::new(0),
+            call_unsafe_fn: AtomicU64::new(0),
+            access_extern_item: AtomicU64::new(0),
+        }
+    }
+
+    fn reset(&self) {
+        self.total_instructions.store(0, Ordering::Relaxed);
+        self.total_unsafe_instructions.store(0, Ordering::Relaxed);
+        self.unsafe_loads.store(0, Ordering::Relaxed);
+        self.unsafe_stores.store(0, Ordering::Relaxed);
+        self.unsafe_calls.store(0, Ordering::Relaxed);
+        self.unsafe_casts.store(0, Ordering::Relaxed);
+        self.unsafe_geps.store(0, Ordering::Relaxed);
+        self.unsafe_others.store(0, Ordering::Relaxed);
+        self.deref_raw_pointer.store(0, Ordering::Relaxed);
+        self.access_mutable_static.store(0, Ordering::Relaxed);
+        self.union_field_access.store(0, Ordering::Relaxed);
+        self.inline_asm.store(0, Ordering::Relaxed);
+        self.call_unsafe_fn.store(0, Ordering::Relaxed);
+        self.access_extern_item.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Fixed, const-initialized table of shards - no allocation, `MAX_THREADS` cap.
+#[cfg(not(feature = "no_std"))]
+static SHARDS: [Shard; MAX_THREADS] = [const { Shard::new() }; MAX_THREADS];
+
+/// One past the highest shard slot ever claimed.
+#[cfg(not(feature = "no_std"))]
+static NEXT_SHARD_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(not(feature = "no_std"))]
+std::thread_local! {
+    /// This thread's claimed shard slot, if any.
+    static THREAD_SHARD_SLOT: Cell<Option<usize>> = Cell::new(None);
+}
+
+/// Return this thread's shard, claiming a fresh slot (or reusing a flushed
+/// one) on first use. Falls back to sharing slot 0 once `MAX_THREADS` is
+/// exceeded, which reintroduces contention only for that rare overflow case.
+#[cfg(not(feature = "no_std"))]
+fn current_shard() -> &'static Shard {
+    THREAD_SHARD_SLOT.with(|slot_cell| {
+        if let Some(i) = slot_cell.get() {
+            return &SHARDS[i];
+        }
+
+        // Try to reclaim a shard left behind by a thread that flushed/exited.
+        let claimed_so_far = NEXT_SHARD_SLOT.load(Ordering::Acquire).min(MAX_THREADS);
+        for i in 0..claimed_so_far {
+            if SHARDS[i].claimed.compare_exchange(
+                false, true, Ordering::AcqRel, Ordering::Acquire
+            ).is_ok() {
+                SHARDS[i].reset();
+                slot_cell.set(Some(i));
+                return &SHARDS[i];
+            }
+        }
+
+        // Otherwise claim the next never-used slot.
+        let i = NEXT_SHARD_SLOT.fetch_add(1, Ordering::Relaxed);
+        if i < MAX_THREADS {
+            SHARDS[i].claimed.store(true, Ordering::Release);
+            slot_cell.set(Some(i));
+            &SHARDS[i]
+        } else {
+            NEXT_SHARD_SLOT.fetch_sub(1, Ordering::Relaxed);
+            slot_cell.set(Some(0));
+            &SHARDS[0]
+        }
+    })
 }
 
 /// Main tracker structure - all fixed-size, no allocations
@@ -67,6 +210,10 @@ struct UnsafeTracker {
     
     // Per-function call counts
     function_calls: [CachePadded<AtomicU64>; MAX_FUNCTIONS],
+
+    // Per-function unsafe-instruction counts, for the "Top N unsafe
+    // functions" hotspot report.
+    function_unsafe_insts: [CachePadded<AtomicU64>; MAX_FUNCTIONS],
     
     // Bitset for tracking which functions were executed
     functions_seen: AtomicBitset,
@@ -77,21 +224,40 @@ struct UnsafeTracker {
     total_instructions: AtomicU64,
     total_unsafe_instructions: AtomicU64,
     
-    // Unsafe instruction type counters (6 categories)
+    // Unsafe instruction type counters (6 categories, raw-opcode based)
     unsafe_loads: AtomicU64,
     unsafe_stores: AtomicU64,
     unsafe_calls: AtomicU64,
     unsafe_casts: AtomicU64,
     unsafe_geps: AtomicU64,
     unsafe_others: AtomicU64,
-    
+
+    // Unsafe operation taxonomy, mirroring rustc's unsafe_op_in_unsafe_fn
+    // categories. These are mutually exclusive by construction, so
+    // `dump_stats` also reports an "unclassified" residual against
+    // `total_unsafe_instructions` to keep the report self-checking.
+    deref_raw_pointer: AtomicU64,
+    access_mutable_static: AtomicU64,
+    union_field_access: AtomicU64,
+    inline_asm: AtomicU64,
+    call_unsafe_fn: AtomicU64,
+    access_extern_item: AtomicU64,
+
     // ===== Control =====
-    
+
     // Ensure stats are written only once
     stats_written: AtomicBool,
-    
+
     // Track if metadata has been initialized
     metadata_initialized: AtomicBool,
+
+    // Mutual exclusion between the final `dump_stats` and a phase
+    // `snapshot`, so a snapshot can't reset counters mid-read by the final
+    // dump (or vice versa). A spinlock rather than a `Mutex` to match the
+    // rest of this tracker's lock-free design; held only for the duration
+    // of a counter read/reset, never across I/O.
+    #[cfg(not(feature = "no_std"))]
+    stats_lock: AtomicBool,
 }
 
 impl UnsafeTracker {
@@ -106,6 +272,7 @@ impl UnsafeTracker {
             metadata: [UNINIT; MAX_FUNCTIONS],
             metadata_count: AtomicU32::new(0),
             function_calls: [ZERO_PADDED; MAX_FUNCTIONS],
+            function_unsafe_insts: [ZERO_PADDED; MAX_FUNCTIONS],
             functions_seen: AtomicBitset::new(),
             
             // Instruction counting
@@ -117,10 +284,18 @@ impl UnsafeTracker {
             unsafe_casts: AtomicU64::new(0),
             unsafe_geps: AtomicU64::new(0),
             unsafe_others: AtomicU64::new(0),
-            
+            deref_raw_pointer: AtomicU64::new(0),
+            access_mutable_static: AtomicU64::new(0),
+            union_field_access: AtomicU64::new(0),
+            inline_asm: AtomicU64::new(0),
+            call_unsafe_fn: AtomicU64::new(0),
+            access_extern_item: AtomicU64::new(0),
+
             // Control
             stats_written: AtomicBool::new(false),
             metadata_initialized: AtomicBool::new(false),
+            #[cfg(not(feature = "no_std"))]
+            stats_lock: AtomicBool::new(false),
         }
     }
     
@@ -135,11 +310,12 @@ impl UnsafeTracker {
         }
         
         if count > MAX_FUNCTIONS as u32 {
+            #[cfg(not(feature = "no_std"))]
             eprintln!("Warning: Function count {} exceeds maximum {}", count, MAX_FUNCTIONS);
             return;
         }
-        
-        let metadata_slice = std::slice::from_raw_parts(
+
+        let metadata_slice = core::slice::from_raw_parts(
             metadata_ptr as *const FunctionMetadata,
             count as usize
         );
@@ -169,10 +345,14 @@ impl UnsafeTracker {
     
     // ===== Functions called by UnsafeInstCounterPass =====
     
-    /// Record basic block statistics - called per basic block
+    /// Record basic block statistics - called per basic block.
+    ///
+    /// Delegates into [`Self::record_block2`] with every semantic-taxonomy
+    /// category set to zero, for callers still emitting the original,
+    /// narrower ABI.
     #[inline(always)]
-    fn record_block(&self, 
-        _func_id: u32,  // Available but not needed
+    fn record_block(&self,
+        func_id: u32,
         total: u32,
         unsafe_total: u32,
         unsafe_load: u16,
@@ -182,56 +362,177 @@ impl UnsafeTracker {
         unsafe_gep: u16,
         unsafe_other: u16
     ) {
-        // Update global counters
-        self.total_instructions.fetch_add(total as u64, Ordering::Relaxed);
-        
+        self.record_block2(
+            func_id, total, unsafe_total,
+            unsafe_load, unsafe_store, unsafe_call, unsafe_cast, unsafe_gep, unsafe_other,
+            0, 0, 0, 0, 0, 0,
+        );
+    }
+
+    /// Record basic block statistics, additionally attributing each counted
+    /// unsafe instruction to the semantic [`UnsafeOpKind`]-style category the
+    /// source-level unsafe site belongs to (raw-pointer deref, mutable-static
+    /// access, union field access, inline `asm!`, call to an `unsafe fn`, or
+    /// access to an `extern` item), rather than just the raw LLVM opcode.
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn record_block2(&self,
+        func_id: u32,
+        total: u32,
+        unsafe_total: u32,
+        unsafe_load: u16,
+        unsafe_store: u16,
+        unsafe_call: u16,
+        unsafe_cast: u16,
+        unsafe_gep: u16,
+        unsafe_other: u16,
+        deref_raw_pointer: u16,
+        access_mutable_static: u16,
+        union_field_access: u16,
+        inline_asm: u16,
+        call_unsafe_fn: u16,
+        access_extern_item: u16,
+    ) {
+        #[cfg(not(feature = "no_std"))]
+        let counters = current_shard();
+        #[cfg(feature = "no_std")]
+        let counters = self;
+
+        // Update counters. Off the `no_std` path this hits a thread-owned
+        // shard, so there is no cross-thread contention here.
+        counters.total_instructions.fetch_add(total as u64, Ordering::Relaxed);
+
         // Early exit if no unsafe instructions
         if unsafe_total == 0 {
             return;
         }
-        
-        self.total_unsafe_instructions.fetch_add(unsafe_total as u64, Ordering::Relaxed);
-        
+
+        counters.total_unsafe_instructions.fetch_add(unsafe_total as u64, Ordering::Relaxed);
+
+        // Per-function accumulation, for the "Top N unsafe functions" report.
+        // This one is global (not sharded) since it's keyed by func_id rather
+        // than by thread.
+        if (func_id as usize) < MAX_FUNCTIONS {
+            self.function_unsafe_insts[func_id as usize].value.fetch_add(unsafe_total as u64, Ordering::Relaxed);
+        }
+
         // Update category counters only if non-zero
         if unsafe_load > 0 {
-            self.unsafe_loads.fetch_add(unsafe_load as u64, Ordering::Relaxed);
+            counters.unsafe_loads.fetch_add(unsafe_load as u64, Ordering::Relaxed);
         }
         if unsafe_store > 0 {
-            self.unsafe_stores.fetch_add(unsafe_store as u64, Ordering::Relaxed);
+            counters.unsafe_stores.fetch_add(unsafe_store as u64, Ordering::Relaxed);
         }
         if unsafe_call > 0 {
-            self.unsafe_calls.fetch_add(unsafe_call as u64, Ordering::Relaxed);
+            counters.unsafe_calls.fetch_add(unsafe_call as u64, Ordering::Relaxed);
         }
         if unsafe_cast > 0 {
-            self.unsafe_casts.fetch_add(unsafe_cast as u64, Ordering::Relaxed);
+            counters.unsafe_casts.fetch_add(unsafe_cast as u64, Ordering::Relaxed);
         }
         if unsafe_gep > 0 {
-            self.unsafe_geps.fetch_add(unsafe_gep as u64, Ordering::Relaxed);
+            counters.unsafe_geps.fetch_add(unsafe_gep as u64, Ordering::Relaxed);
         }
         if unsafe_other > 0 {
-            self.unsafe_others.fetch_add(unsafe_other as u64, Ordering::Relaxed);
+            counters.unsafe_others.fetch_add(unsafe_other as u64, Ordering::Relaxed);
+        }
+        if deref_raw_pointer > 0 {
+            counters.deref_raw_pointer.fetch_add(deref_raw_pointer as u64, Ordering::Relaxed);
+        }
+        if access_mutable_static > 0 {
+            counters.access_mutable_static.fetch_add(access_mutable_static as u64, Ordering::Relaxed);
+        }
+        if union_field_access > 0 {
+            counters.union_field_access.fetch_add(union_field_access as u64, Ordering::Relaxed);
+        }
+        if inline_asm > 0 {
+            counters.inline_asm.fetch_add(inline_asm as u64, Ordering::Relaxed);
+        }
+        if call_unsafe_fn > 0 {
+            counters.call_unsafe_fn.fetch_add(call_unsafe_fn as u64, Ordering::Relaxed);
+        }
+        if access_extern_item > 0 {
+            counters.access_extern_item.fetch_add(access_extern_item as u64, Ordering::Relaxed);
         }
     }
-    
-    // ===== Statistics Output =====
-    
-    /// Calculate and dump statistics
-    fn dump_stats(&self) {
-        // Ensure single execution
-        if self.stats_written.swap(true, Ordering::AcqRel) {
-            return;
+
+    /// Fold one shard's counters into the global totals, zeroing the shard
+    /// as we go so repeated folds (dump + per-thread flush) never double-count.
+    #[cfg(not(feature = "no_std"))]
+    fn fold_shard_into_totals(&self, shard: &Shard) {
+        self.total_instructions.fetch_add(shard.total_instructions.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.total_unsafe_instructions.fetch_add(shard.total_unsafe_instructions.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.unsafe_loads.fetch_add(shard.unsafe_loads.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.unsafe_stores.fetch_add(shard.unsafe_stores.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.unsafe_calls.fetch_add(shard.unsafe_calls.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.unsafe_casts.fetch_add(shard.unsafe_casts.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.unsafe_geps.fetch_add(shard.unsafe_geps.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.unsafe_others.fetch_add(shard.unsafe_others.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.deref_raw_pointer.fetch_add(shard.deref_raw_pointer.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.access_mutable_static.fetch_add(shard.access_mutable_static.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.union_field_access.fetch_add(shard.union_field_access.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.inline_asm.fetch_add(shard.inline_asm.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.call_unsafe_fn.fetch_add(shard.call_unsafe_fn.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+        self.access_extern_item.fetch_add(shard.access_extern_item.swap(0, Ordering::AcqRel), Ordering::Relaxed);
+    }
+
+    /// Fold every claimed shard into the global totals. Called from
+    /// `dump_stats` and from each `snapshot` before the totals are read.
+    #[cfg(not(feature = "no_std"))]
+    fn fold_all_shards(&self) {
+        let claimed_so_far = NEXT_SHARD_SLOT.load(Ordering::Acquire).min(MAX_THREADS);
+        for i in 0..claimed_so_far {
+            self.fold_shard_into_totals(&SHARDS[i]);
         }
-        
-        // Check if metadata was initialized
-        if !self.metadata_initialized.load(Ordering::Acquire) {
-            return;
+    }
+
+    /// Build the "Top N unsafe functions" list, ranked by unsafe-instruction
+    /// count (ties broken by call count), resolving each slot back to its
+    /// `FunctionMetadata`. Only functions seen at runtime with at least one
+    /// unsafe instruction are eligible.
+    #[cfg(not(feature = "no_std"))]
+    fn top_unsafe_functions(&self, metadata_count: usize, n: usize) -> Vec<HotFunction> {
+        let mut entries: Vec<HotFunction> = Vec::new();
+        for i in 0..metadata_count {
+            if !self.functions_seen.is_set(i) {
+                continue;
+            }
+            let unsafe_insts = self.function_unsafe_insts[i].value.load(Ordering::Relaxed);
+            if unsafe_insts == 0 {
+                continue;
+            }
+            let meta = unsafe { self.metadata[i].assume_init() };
+            let calls = self.function_calls[i].value.load(Ordering::Relaxed);
+            entries.push(HotFunction { func_id: meta.id, unsafe_insts, calls });
         }
-        
-        let metadata_count = self.metadata_count.load(Ordering::Acquire) as usize;
-        if metadata_count == 0 {
-            return;
+        entries.sort_by(|a, b| b.unsafe_insts.cmp(&a.unsafe_insts).then(b.calls.cmp(&a.calls)));
+        entries.truncate(n);
+        entries
+    }
+
+    // ===== Statistics Output =====
+
+    /// Spin until `stats_lock` is ours. Only ever held for the duration of
+    /// a counter read (and optional reset), never across I/O.
+    #[cfg(not(feature = "no_std"))]
+    fn acquire_stats_lock(&self) {
+        while self
+            .stats_lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
         }
-        
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    fn release_stats_lock(&self) {
+        self.stats_lock.store(false, Ordering::Release);
+    }
+
+    /// Read the current counters into a `DumpCounters` snapshot, without
+    /// writing anything out. Shared by `dump_stats` and `snapshot`. Callers
+    /// are responsible for folding shards and holding `stats_lock` first.
+    fn compute_counters(&self, metadata_count: usize) -> DumpCounters {
         // Load instruction statistics
         let total_insts = self.total_instructions.load(Ordering::Relaxed);
         let unsafe_insts = self.total_unsafe_instructions.load(Ordering::Relaxed);
@@ -241,74 +542,679 @@ impl UnsafeTracker {
         let unsafe_casts = self.unsafe_casts.load(Ordering::Relaxed);
         let unsafe_geps = self.unsafe_geps.load(Ordering::Relaxed);
         let unsafe_others = self.unsafe_others.load(Ordering::Relaxed);
-        
+
+        // Load the semantic unsafe-operation taxonomy
+        let deref_raw_pointer = self.deref_raw_pointer.load(Ordering::Relaxed);
+        let access_mutable_static = self.access_mutable_static.load(Ordering::Relaxed);
+        let union_field_access = self.union_field_access.load(Ordering::Relaxed);
+        let inline_asm = self.inline_asm.load(Ordering::Relaxed);
+        let call_unsafe_fn = self.call_unsafe_fn.load(Ordering::Relaxed);
+        let access_extern_item = self.access_extern_item.load(Ordering::Relaxed);
+        // These categories are mutually exclusive, so whatever is left over
+        // was counted as unsafe but never attributed to a semantic class.
+        let classified = deref_raw_pointer + access_mutable_static + union_field_access
+            + inline_asm + call_unsafe_fn + access_extern_item;
+        let unclassified = unsafe_insts.saturating_sub(classified);
+
         // Calculate function statistics
         let mut unique_functions = 0u32;
         let mut unique_unsafe_functions = 0u32;
         let mut total_function_calls = 0u64;
         let mut unsafe_function_calls = 0u64;
-        
+
         for i in 0..metadata_count {
             if self.functions_seen.is_set(i) {
                 unique_functions += 1;
-                
+
                 // Get metadata for this function
                 let meta = unsafe { self.metadata[i].assume_init() };
                 let is_unsafe = meta.has_unsafe_inst != 0 || meta.has_unsafe_regions != 0;
-                
+
                 if is_unsafe {
                     unique_unsafe_functions += 1;
                 }
-                
+
                 // Get call count
                 let calls = self.function_calls[i].value.load(Ordering::Relaxed);
                 total_function_calls += calls;
-                
+
                 if is_unsafe {
                     unsafe_function_calls += calls;
                 }
             }
         }
-        
-        // Format output in simple format
-        let output = format!(
-            concat!(
-                "Total instructions: {}\n",
-                "Unsafe instructions: {}\n",
-                "Unsafe loads: {}\n",
-                "Unsafe stores: {}\n",
-                "Unsafe calls: {}\n",
-                "Unsafe casts: {}\n",
-                "Unsafe GEPs: {}\n",
-                "Unsafe others: {}\n",
-                "Unique functions: {}\n",
-                "Unique unsafe functions: {}\n",
-                "Total function calls: {}\n",
-                "Unsafe function calls: {}\n"
-            ),
-            total_insts,
-            unsafe_insts,
-            unsafe_loads,
-            unsafe_stores,
-            unsafe_calls_inst,
-            unsafe_casts,
-            unsafe_geps,
-            unsafe_others,
-            unique_functions,
-            unique_unsafe_functions,
-            total_function_calls,
-            unsafe_function_calls
-        );
-        
-        // Write to file
-        let _ = write_output(&output, "unsafe_counter.stat");
-        
-        if cfg!(debug_assertions) {
-            eprintln!("{}", output);
+
+        DumpCounters {
+            total_insts, unsafe_insts, unsafe_loads, unsafe_stores,
+            unsafe_calls_inst, unsafe_casts, unsafe_geps, unsafe_others,
+            deref_raw_pointer, access_mutable_static, union_field_access,
+            inline_asm, call_unsafe_fn, access_extern_item, unclassified,
+            unique_functions, unique_unsafe_functions,
+            total_function_calls, unsafe_function_calls,
+            #[cfg(not(feature = "no_std"))]
+            top_unsafe_functions: self.top_unsafe_functions(metadata_count, top_n_from_env()),
+        }
+    }
+
+    /// Zero every numeric counter and the `functions_seen`/per-function
+    /// tables, so the next phase's `snapshot` or final `dump_stats` reports
+    /// only what happens after this point. Caller must hold `stats_lock`.
+    #[cfg(not(feature = "no_std"))]
+    fn reset_counters(&self, metadata_count: usize) {
+        self.total_instructions.store(0, Ordering::Relaxed);
+        self.total_unsafe_instructions.store(0, Ordering::Relaxed);
+        self.unsafe_loads.store(0, Ordering::Relaxed);
+        self.unsafe_stores.store(0, Ordering::Relaxed);
+        self.unsafe_calls.store(0, Ordering::Relaxed);
+        self.unsafe_casts.store(0, Ordering::Relaxed);
+        self.unsafe_geps.store(0, Ordering::Relaxed);
+        self.unsafe_others.store(0, Ordering::Relaxed);
+        self.deref_raw_pointer.store(0, Ordering::Relaxed);
+        self.access_mutable_static.store(0, Ordering::Relaxed);
+        self.union_field_access.store(0, Ordering::Relaxed);
+        self.inline_asm.store(0, Ordering::Relaxed);
+        self.call_unsafe_fn.store(0, Ordering::Relaxed);
+        self.access_extern_item.store(0, Ordering::Relaxed);
+
+        for i in 0..metadata_count {
+            self.function_calls[i].value.store(0, Ordering::Relaxed);
+            self.function_unsafe_insts[i].value.store(0, Ordering::Relaxed);
+            self.functions_seen.clear(i);
+        }
+    }
+
+    /// Emit a labeled snapshot of the counters so far, for a long-running
+    /// process that never hits the final `#[dtor]` (a server, a REPL, a
+    /// multi-iteration benchmark). Mutually exclusive with `dump_stats` via
+    /// `stats_lock`, so a snapshot can't reset counters out from under the
+    /// final dump or vice versa. When `reset` is set, counters and
+    /// `functions_seen` are zeroed afterward so the next phase starts fresh.
+    #[cfg(not(feature = "no_std"))]
+    fn snapshot(&self, label: &str, reset: bool) {
+        // A snapshot after the final dump has nothing left to report.
+        if self.stats_written.load(Ordering::Acquire) {
+            return;
+        }
+        if !self.metadata_initialized.load(Ordering::Acquire) {
+            return;
+        }
+
+        self.acquire_stats_lock();
+
+        // Re-check after acquiring the lock: `dump_stats` may have run
+        // (and folded/reported) while we were spinning.
+        if self.stats_written.load(Ordering::Acquire) {
+            self.release_stats_lock();
+            return;
         }
+
+        let metadata_count = self.metadata_count.load(Ordering::Acquire) as usize;
+        self.fold_all_shards();
+        let counters = self.compute_counters(metadata_count);
+        if reset {
+            self.reset_counters(metadata_count);
+        }
+
+        self.release_stats_lock();
+
+        let (content, filename) = snapshot_content(label, &counters);
+        emit_via_sink(&content, filename);
+    }
+
+    /// Calculate and dump statistics
+    fn dump_stats(&self) {
+        // Ensure single execution
+        if self.stats_written.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        // Check if metadata was initialized
+        if !self.metadata_initialized.load(Ordering::Acquire) {
+            return;
+        }
+
+        let metadata_count = self.metadata_count.load(Ordering::Acquire) as usize;
+        if metadata_count == 0 {
+            return;
+        }
+
+        #[cfg(not(feature = "no_std"))]
+        self.acquire_stats_lock();
+
+        // Fold every thread's shard into the global totals before reporting.
+        #[cfg(not(feature = "no_std"))]
+        self.fold_all_shards();
+
+        let counters = self.compute_counters(metadata_count);
+
+        #[cfg(not(feature = "no_std"))]
+        self.release_stats_lock();
+
+        #[cfg(not(feature = "no_std"))]
+        emit_stats_std(&counters);
+        #[cfg(feature = "no_std")]
+        emit_stats_no_std(&counters);
+    }
+}
+
+/// Plain snapshot of the counters computed by `dump_stats`, decoupled from
+/// how they end up getting formatted/emitted (std vs. `no_std`).
+struct DumpCounters {
+    total_insts: u64,
+    unsafe_insts: u64,
+    unsafe_loads: u64,
+    unsafe_stores: u64,
+    unsafe_calls_inst: u64,
+    unsafe_casts: u64,
+    unsafe_geps: u64,
+    unsafe_others: u64,
+    deref_raw_pointer: u64,
+    access_mutable_static: u64,
+    union_field_access: u64,
+    inline_asm: u64,
+    call_unsafe_fn: u64,
+    access_extern_item: u64,
+    unclassified: u64,
+    unique_functions: u32,
+    unique_unsafe_functions: u32,
+    total_function_calls: u64,
+    unsafe_function_calls: u64,
+    #[cfg(not(feature = "no_std"))]
+    top_unsafe_functions: Vec<HotFunction>,
+}
+
+/// One entry in the "Top N unsafe functions" report.
+#[cfg(not(feature = "no_std"))]
+struct HotFunction {
+    func_id: u32,
+    unsafe_insts: u64,
+    calls: u64,
+}
+
+/// Number of functions to list in the hotspot report by default, when
+/// `UNSAFE_TOP_N` is unset or unparseable.
+#[cfg(not(feature = "no_std"))]
+const DEFAULT_TOP_N: usize = 10;
+
+/// Read the hotspot report size from `UNSAFE_TOP_N`, once, at dump time.
+#[cfg(not(feature = "no_std"))]
+fn top_n_from_env() -> usize {
+    std::env::var("UNSAFE_TOP_N")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_TOP_N)
+}
+
+/// Text (original) rendering of the counters, plus the hotspot report.
+#[cfg(not(feature = "no_std"))]
+fn text_stats(counters: &DumpCounters) -> String {
+    let mut output = format!(
+        concat!(
+            "Total instructions: {}\n",
+            "Unsafe instructions: {}\n",
+            "Unsafe loads: {}\n",
+            "Unsafe stores: {}\n",
+            "Unsafe calls: {}\n",
+            "Unsafe casts: {}\n",
+            "Unsafe GEPs: {}\n",
+            "Unsafe others: {}\n",
+            "Deref raw pointer: {}\n",
+            "Access mutable static: {}\n",
+            "Union field access: {}\n",
+            "Inline asm: {}\n",
+            "Call unsafe fn: {}\n",
+            "Access extern item: {}\n",
+            "Unclassified unsafe: {}\n",
+            "Unique functions: {}\n",
+            "Unique unsafe functions: {}\n",
+            "Total function calls: {}\n",
+            "Unsafe function calls: {}\n"
+        ),
+        counters.total_insts,
+        counters.unsafe_insts,
+        counters.unsafe_loads,
+        counters.unsafe_stores,
+        counters.unsafe_calls_inst,
+        counters.unsafe_casts,
+        counters.unsafe_geps,
+        counters.unsafe_others,
+        counters.deref_raw_pointer,
+        counters.access_mutable_static,
+        counters.union_field_access,
+        counters.inline_asm,
+        counters.call_unsafe_fn,
+        counters.access_extern_item,
+        counters.unclassified,
+        counters.unique_functions,
+        counters.unique_unsafe_functions,
+        counters.total_function_calls,
+        counters.unsafe_function_calls
+    );
+
+    if !counters.top_unsafe_functions.is_empty() {
+        output.push_str(&format!("Top {} unsafe functions (by unsafe instructions):\n", counters.top_unsafe_functions.len()));
+        for (rank, f) in counters.top_unsafe_functions.iter().enumerate() {
+            output.push_str(&format!(
+                "  #{} func_id={} unsafe_insts={} calls={}\n",
+                rank + 1, f.func_id, f.unsafe_insts, f.calls
+            ));
+        }
+    }
+
+    output
+}
+
+/// JSON rendering: the full counter set as an object, plus the per-function
+/// hotspot array, so downstream tooling can diff runs programmatically.
+/// Hand-rolled (no serde) to keep this runtime's dependency footprint small.
+#[cfg(not(feature = "no_std"))]
+fn json_stats(counters: &DumpCounters) -> String {
+    let mut functions = String::new();
+    for (i, f) in counters.top_unsafe_functions.iter().enumerate() {
+        if i > 0 {
+            functions.push(',');
+        }
+        functions.push_str(&format!(
+            "{{\"func_id\":{},\"unsafe_insts\":{},\"calls\":{}}}",
+            f.func_id, f.unsafe_insts, f.calls
+        ));
+    }
+
+    format!(
+        concat!(
+            "{{",
+            "\"total_instructions\":{},",
+            "\"unsafe_instructions\":{},",
+            "\"unsafe_loads\":{},",
+            "\"unsafe_stores\":{},",
+            "\"unsafe_calls\":{},",
+            "\"unsafe_casts\":{},",
+            "\"unsafe_geps\":{},",
+            "\"unsafe_others\":{},",
+            "\"deref_raw_pointer\":{},",
+            "\"access_mutable_static\":{},",
+            "\"union_field_access\":{},",
+            "\"inline_asm\":{},",
+            "\"call_unsafe_fn\":{},",
+            "\"access_extern_item\":{},",
+            "\"unclassified_unsafe\":{},",
+            "\"unique_functions\":{},",
+            "\"unique_unsafe_functions\":{},",
+            "\"total_function_calls\":{},",
+            "\"unsafe_function_calls\":{},",
+            "\"functions\":[{}]",
+            "}}\n",
+        ),
+        counters.total_insts,
+        counters.unsafe_insts,
+        counters.unsafe_loads,
+        counters.unsafe_stores,
+        counters.unsafe_calls_inst,
+        counters.unsafe_casts,
+        counters.unsafe_geps,
+        counters.unsafe_others,
+        counters.deref_raw_pointer,
+        counters.access_mutable_static,
+        counters.union_field_access,
+        counters.inline_asm,
+        counters.call_unsafe_fn,
+        counters.access_extern_item,
+        counters.unclassified,
+        counters.unique_functions,
+        counters.unique_unsafe_functions,
+        counters.total_function_calls,
+        counters.unsafe_function_calls,
+        functions,
+    )
+}
+
+/// CSV rendering: one `metric,value` row per aggregate counter, followed by
+/// a `func_id,unsafe_insts,calls` table for the hotspot report.
+#[cfg(not(feature = "no_std"))]
+fn csv_stats(counters: &DumpCounters) -> String {
+    let mut out = String::new();
+    out.push_str("metric,value\n");
+    out.push_str(&format!("total_instructions,{}\n", counters.total_insts));
+    out.push_str(&format!("unsafe_instructions,{}\n", counters.unsafe_insts));
+    out.push_str(&format!("unsafe_loads,{}\n", counters.unsafe_loads));
+    out.push_str(&format!("unsafe_stores,{}\n", counters.unsafe_stores));
+    out.push_str(&format!("unsafe_calls,{}\n", counters.unsafe_calls_inst));
+    out.push_str(&format!("unsafe_casts,{}\n", counters.unsafe_casts));
+    out.push_str(&format!("unsafe_geps,{}\n", counters.unsafe_geps));
+    out.push_str(&format!("unsafe_others,{}\n", counters.unsafe_others));
+    out.push_str(&format!("deref_raw_pointer,{}\n", counters.deref_raw_pointer));
+    out.push_str(&format!("access_mutable_static,{}\n", counters.access_mutable_static));
+    out.push_str(&format!("union_field_access,{}\n", counters.union_field_access));
+    out.push_str(&format!("inline_asm,{}\n", counters.inline_asm));
+    out.push_str(&format!("call_unsafe_fn,{}\n", counters.call_unsafe_fn));
+    out.push_str(&format!("access_extern_item,{}\n", counters.access_extern_item));
+    out.push_str(&format!("unclassified_unsafe,{}\n", counters.unclassified));
+    out.push_str(&format!("unique_functions,{}\n", counters.unique_functions));
+    out.push_str(&format!("unique_unsafe_functions,{}\n", counters.unique_unsafe_functions));
+    out.push_str(&format!("total_function_calls,{}\n", counters.total_function_calls));
+    out.push_str(&format!("unsafe_function_calls,{}\n", counters.unsafe_function_calls));
+
+    out.push_str("\nfunc_id,unsafe_insts,calls\n");
+    for f in &counters.top_unsafe_functions {
+        out.push_str(&format!("{},{},{}\n", f.func_id, f.unsafe_insts, f.calls));
+    }
+
+    out
+}
+
+/// Selects how `dump_stats` renders its output.
+#[cfg(not(feature = "no_std"))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+/// Selects where `dump_stats` sends its rendered output.
+#[cfg(not(feature = "no_std"))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputSink {
+    /// Append to a file in `UNSAFE_BENCH_OUTPUT_DIR` (the original behavior).
+    File,
+    Stderr,
+    /// Write into an anonymous `memfd`, so a parent harness (fuzzer, CI
+    /// wrapper, sandbox) can slurp results via the exported fd without a
+    /// temp file on disk. See `__unsafe_get_memfd`.
+    Memfd,
+}
+
+/// Overrides `UNSAFE_COUNTER_OUTPUT_FORMAT` when set via
+/// `__unsafe_set_output_format`; `u32::MAX` means "no override, check env".
+#[cfg(not(feature = "no_std"))]
+static OUTPUT_FORMAT_OVERRIDE: AtomicU32 = AtomicU32::new(u32::MAX);
+
+#[cfg(not(feature = "no_std"))]
+fn output_format() -> OutputFormat {
+    let code = match OUTPUT_FORMAT_OVERRIDE.load(Ordering::Acquire) {
+        u32::MAX => std::env::var("UNSAFE_COUNTER_OUTPUT_FORMAT")
+            .ok()
+            .map(|v| format_code(&v))
+            // `UNSAFE_COUNTER_OUTPUT_FORMAT` is unset: fall back to the
+            // shared `UNSAFE_BENCH_OUTPUT_FORMAT=json` switch so enabling
+            // structured output once turns on JSON for every monitor module.
+            .unwrap_or_else(|| if crate::structured_output_enabled() { 1 } else { 0 }),
+        code => code,
+    };
+    match code {
+        1 => OutputFormat::Json,
+        2 => OutputFormat::Csv,
+        _ => OutputFormat::Text,
+    }
+}
+
+#[cfg(not(feature = "no_std"))]
+fn format_code(name: &str) -> u32 {
+    match name.to_ascii_lowercase().as_str() {
+        "json" => 1,
+        "csv" => 2,
+        _ => 0,
+    }
+}
+
+#[cfg(not(feature = "no_std"))]
+fn output_sink() -> OutputSink {
+    match std::env::var("UNSAFE_COUNTER_OUTPUT_SINK").ok().as_deref() {
+        Some("stderr") => OutputSink::Stderr,
+        Some("memfd") => OutputSink::Memfd,
+        _ => OutputSink::File,
+    }
+}
+
+/// Anonymous memfd created lazily for `OutputSink::Memfd`, or -1 if none has
+/// been created yet.
+#[cfg(not(feature = "no_std"))]
+static MEMFD: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(-1);
+
+/// Lazily create the output memfd, returning its fd (or -1 on failure).
+#[cfg(not(feature = "no_std"))]
+fn get_or_create_memfd() -> i32 {
+    let existing = MEMFD.load(Ordering::Acquire);
+    if existing >= 0 {
+        return existing;
+    }
+
+    let name = b"unsafe_counter.stat\0";
+    let fd = unsafe { libc::memfd_create(name.as_ptr() as *const std::os::raw::c_char, 0) };
+    if fd < 0 {
+        return -1;
+    }
+
+    match MEMFD.compare_exchange(-1, fd, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => fd,
+        Err(winner) => {
+            // Another thread created one first; drop ours.
+            unsafe { libc::close(fd) };
+            winner
+        }
+    }
+}
+
+/// Route rendered output through the configured sink.
+#[cfg(not(feature = "no_std"))]
+fn emit_via_sink(content: &str, filename: &str) {
+    match output_sink() {
+        OutputSink::File => {
+            let _ = write_output(content, filename);
+        }
+        OutputSink::Stderr => {
+            eprintln!("{}", content);
+        }
+        OutputSink::Memfd => {
+            let fd = get_or_create_memfd();
+            if fd >= 0 {
+                unsafe {
+                    libc::write(fd, content.as_ptr() as *const std::os::raw::c_void, content.len());
+                }
+            }
+        }
+    }
+}
+
+/// Return the fd of the memfd output sink, creating it if necessary (whether
+/// or not `OutputSink::Memfd` is selected). Returns -1 if `memfd_create`
+/// failed.
+#[cfg(not(feature = "no_std"))]
+#[no_mangle]
+pub extern "C" fn __unsafe_get_memfd() -> i32 {
+    get_or_create_memfd()
+}
+
+/// Override the output format selected by `UNSAFE_COUNTER_OUTPUT_FORMAT`;
+/// `0` = text, `1` = JSON, `2` = CSV.
+#[cfg(not(feature = "no_std"))]
+#[no_mangle]
+pub extern "C" fn __unsafe_set_output_format(format: u32) {
+    OUTPUT_FORMAT_OVERRIDE.store(format, Ordering::Release);
+}
+
+#[cfg(not(feature = "no_std"))]
+fn emit_stats_std(counters: &DumpCounters) {
+    let format = output_format();
+    let (content, filename) = match format {
+        OutputFormat::Text => (text_stats(counters), "unsafe_counter.stat"),
+        OutputFormat::Json => (json_stats(counters), "unsafe_counter.json"),
+        OutputFormat::Csv => (csv_stats(counters), "unsafe_counter.csv"),
+    };
+
+    emit_via_sink(&content, filename);
+
+    if cfg!(debug_assertions) && format == OutputFormat::Text {
+        eprintln!("{}", content);
+    }
+}
+
+/// Render one phase/interval snapshot: the counters in whichever format
+/// `output_format()` currently selects, with `label` spliced in so a
+/// sequence of snapshots reads as a labeled time series. Written to a
+/// distinct `*.snapshot.*` file so it never collides with the final
+/// `dump_stats` output even when both use `OutputSink::File`.
+#[cfg(not(feature = "no_std"))]
+fn snapshot_content(label: &str, counters: &DumpCounters) -> (String, &'static str) {
+    match output_format() {
+        OutputFormat::Json => {
+            let body = json_stats(counters);
+            // json_stats ends with "}\n"; splice the label in as the first
+            // field of the same object rather than prefixing a line, so the
+            // snapshot stays a single parseable JSON record.
+            let body = body.trim_end().strip_prefix('{').unwrap_or(&body);
+            (format!("{{\"label\":{:?},{}\n", label, body), "unsafe_counter.snapshot.json")
+        }
+        OutputFormat::Csv => (
+            format!("# label={}\n{}", label, csv_stats(counters)),
+            "unsafe_counter.snapshot.csv",
+        ),
+        OutputFormat::Text => (
+            format!("=== SNAPSHOT {} ===\n{}", label, text_stats(counters)),
+            "unsafe_counter.snapshot.stat",
+        ),
     }
 }
 
+/// Whether `__unsafe_snapshot_stats` should zero the counters after
+/// emitting, so the next phase starts fresh. Defaults to on, since a
+/// snapshot's whole point is per-phase (not cumulative) reporting; set
+/// `UNSAFE_COUNTER_SNAPSHOT_RESET=0` to keep accumulating across snapshots.
+#[cfg(not(feature = "no_std"))]
+fn snapshot_reset_enabled() -> bool {
+    !matches!(
+        std::env::var("UNSAFE_COUNTER_SNAPSHOT_RESET").as_deref(),
+        Ok("0") | Ok("false")
+    )
+}
+
+/// `no_std` dump path: format the counters into a fixed on-stack byte buffer
+/// (no `format!`, no allocation) and hand the result to the caller-registered
+/// sink callback, if one has been set via `__unsafe_set_stats_sink`.
+#[cfg(feature = "no_std")]
+fn emit_stats_no_std(counters: &DumpCounters) {
+    let mut buf = StatsBuffer::new();
+    buf.push_str("Total instructions: ");
+    buf.push_u64(counters.total_insts);
+    buf.push_str("\nUnsafe instructions: ");
+    buf.push_u64(counters.unsafe_insts);
+    buf.push_str("\nUnsafe loads: ");
+    buf.push_u64(counters.unsafe_loads);
+    buf.push_str("\nUnsafe stores: ");
+    buf.push_u64(counters.unsafe_stores);
+    buf.push_str("\nUnsafe calls: ");
+    buf.push_u64(counters.unsafe_calls_inst);
+    buf.push_str("\nUnsafe casts: ");
+    buf.push_u64(counters.unsafe_casts);
+    buf.push_str("\nUnsafe GEPs: ");
+    buf.push_u64(counters.unsafe_geps);
+    buf.push_str("\nUnsafe others: ");
+    buf.push_u64(counters.unsafe_others);
+    buf.push_str("\nDeref raw pointer: ");
+    buf.push_u64(counters.deref_raw_pointer);
+    buf.push_str("\nAccess mutable static: ");
+    buf.push_u64(counters.access_mutable_static);
+    buf.push_str("\nUnion field access: ");
+    buf.push_u64(counters.union_field_access);
+    buf.push_str("\nInline asm: ");
+    buf.push_u64(counters.inline_asm);
+    buf.push_str("\nCall unsafe fn: ");
+    buf.push_u64(counters.call_unsafe_fn);
+    buf.push_str("\nAccess extern item: ");
+    buf.push_u64(counters.access_extern_item);
+    buf.push_str("\nUnclassified unsafe: ");
+    buf.push_u64(counters.unclassified);
+    buf.push_str("\nUnique functions: ");
+    buf.push_u64(counters.unique_functions as u64);
+    buf.push_str("\nUnique unsafe functions: ");
+    buf.push_u64(counters.unique_unsafe_functions as u64);
+    buf.push_str("\nTotal function calls: ");
+    buf.push_u64(counters.total_function_calls);
+    buf.push_str("\nUnsafe function calls: ");
+    buf.push_u64(counters.unsafe_function_calls);
+    buf.push_str("\n");
+
+    emit_to_sink(buf.as_bytes());
+}
+
+/// Maximum size of the stack-allocated formatting buffer used by the
+/// `no_std` dump path; large enough for every counter line with room to spare.
+#[cfg(feature = "no_std")]
+const STATS_BUFFER_SIZE: usize = 768;
+
+/// Minimal no-alloc string builder over a fixed-size stack buffer, used only
+/// when `no_std` is enabled and `format!`/`String` are unavailable.
+#[cfg(feature = "no_std")]
+struct StatsBuffer {
+    buf: [u8; STATS_BUFFER_SIZE],
+    len: usize,
+}
+
+#[cfg(feature = "no_std")]
+impl StatsBuffer {
+    fn new() -> Self {
+        Self { buf: [0; STATS_BUFFER_SIZE], len: 0 }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        let bytes = s.as_bytes();
+        let remaining = STATS_BUFFER_SIZE - self.len;
+        let n = bytes.len().min(remaining);
+        self.buf[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+        self.len += n;
+    }
+
+    fn push_u64(&mut self, mut value: u64) {
+        // Longest u64 is 20 digits.
+        let mut digits = [0u8; 20];
+        let mut n = 0;
+        if value == 0 {
+            digits[0] = b'0';
+            n = 1;
+        } else {
+            while value > 0 {
+                digits[n] = b'0' + (value % 10) as u8;
+                value /= 10;
+                n += 1;
+            }
+        }
+        let remaining = STATS_BUFFER_SIZE - self.len;
+        let write_n = n.min(remaining);
+        for i in 0..write_n {
+            self.buf[self.len + i] = digits[n - 1 - i];
+        }
+        self.len += write_n;
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// Caller-registered C-ABI sink used by the `no_std` dump path in place of
+/// the file-based `write_output`. Stored as a raw function-pointer bit
+/// pattern so it can live in a `static` without requiring `std`.
+#[cfg(feature = "no_std")]
+static STATS_SINK: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "no_std")]
+fn emit_to_sink(bytes: &[u8]) {
+    let sink = STATS_SINK.load(Ordering::Acquire);
+    if sink == 0 {
+        return;
+    }
+    let sink: extern "C" fn(*const u8, usize) = unsafe { core::mem::transmute(sink) };
+    sink(bytes.as_ptr(), bytes.len());
+}
+
+/// Register a C-ABI callback to receive formatted stats from the `no_std`
+/// dump path, e.g. `__unsafe_set_stats_sink(my_uart_writer)`.
+#[cfg(feature = "no_std")]
+#[no_mangle]
+pub extern "C" fn __unsafe_set_stats_sink(sink: extern "C" fn(*const u8, usize)) {
+    STATS_SINK.store(sink as usize, Ordering::Release);
+}
+
 // Global tracker instance - const initialized, no allocation
 static TRACKER: UnsafeTracker = UnsafeTracker::new();
 
@@ -349,6 +1255,40 @@ pub unsafe extern "C" fn __unsafe_record_block(
     );
 }
 
+/// Record basic block statistics, attributing unsafe instructions to the
+/// semantic operation class of the source-level unsafe site they came from
+/// (raw-pointer deref, mutable-static access, union field access, inline
+/// `asm!`, call to an `unsafe fn`, or access to an `extern` item).
+/// Called by UnsafeInstCounterPass for each basic block once it has been
+/// updated to emit the finer-grained taxonomy.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn __unsafe_record_block2(
+    func_id: u32,
+    total: u32,
+    unsafe_total: u32,
+    unsafe_load: u16,
+    unsafe_store: u16,
+    unsafe_call: u16,
+    unsafe_cast: u16,
+    unsafe_gep: u16,
+    unsafe_other: u16,
+    deref_raw_pointer: u16,
+    access_mutable_static: u16,
+    union_field_access: u16,
+    inline_asm: u16,
+    call_unsafe_fn: u16,
+    access_extern_item: u16,
+) {
+    TRACKER.record_block2(
+        func_id, total, unsafe_total,
+        unsafe_load, unsafe_store, unsafe_call,
+        unsafe_cast, unsafe_gep, unsafe_other,
+        deref_raw_pointer, access_mutable_static, union_field_access,
+        inline_asm, call_unsafe_fn, access_extern_item,
+    );
+}
+
 /// Dump statistics at program termination
 /// Called by UnsafeFunctionTrackerPass via module destructor
 #[no_mangle]
@@ -356,8 +1296,50 @@ pub unsafe extern "C" fn __unsafe_dump_stats() {
     TRACKER.dump_stats();
 }
 
-/// Automatic cleanup at program exit (backup)
-#[ctor::dtor]
+/// Emit a labeled snapshot of the counters accumulated so far, and reset
+/// them for the next phase (unless `UNSAFE_COUNTER_SNAPSHOT_RESET=0`).
+/// Intended for long-running processes (servers, REPLs, multi-iteration
+/// benchmarks) that never hit the `#[dtor]`-driven final dump, where a
+/// single lifetime total would hide per-phase unsafe-instruction rates.
+/// `label` must be a NUL-terminated string valid for the duration of this
+/// call; non-UTF-8 labels are rendered as `<invalid>`.
+#[cfg(not(feature = "no_std"))]
+#[no_mangle]
+pub unsafe extern "C" fn __unsafe_snapshot_stats(label: *const u8) {
+    let label = if label.is_null() {
+        "<unknown>"
+    } else {
+        std::ffi::CStr::from_ptr(label as *const std::os::raw::c_char)
+            .to_str()
+            .unwrap_or("<invalid>")
+    };
+    TRACKER.snapshot(label, snapshot_reset_enabled());
+}
+
+/// Fold the calling thread's shard into the global totals now and release
+/// its slot for reuse by a later thread. A shard that is never flushed keeps
+/// its counts and is folded at the final dump like any other claimed shard.
+#[cfg(not(feature = "no_std"))]
+#[no_mangle]
+pub extern "C" fn __unsafe_flush_thread() {
+    THREAD_SHARD_SLOT.with(|slot_cell| {
+        if let Some(i) = slot_cell.get() {
+            TRACKER.fold_shard_into_totals(&SHARDS[i]);
+            SHARDS[i].claimed.store(false, Ordering::Release);
+            slot_cell.set(None);
+        }
+    });
+}
+
+/// Automatic cleanup at program exit (backup), run via the crate's unified
+/// shutdown coordinator (`register_at_exit`) rather than our own
+/// `#[ctor::dtor]`, so this flushes in a single, ordered place alongside
+/// every other feature module.
 fn cleanup() {
     TRACKER.dump_stats();
+}
+
+#[ctor::ctor]
+fn register_unsafe_counter_shutdown() {
+    crate::register_at_exit(cleanup);
 }
\ No newline at end of file
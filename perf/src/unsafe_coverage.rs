@@ -1,13 +1,30 @@
 //! Unsafe Line Coverage Runtime Library
 //! Track: total unsafe lines (compilation) vs executed unsafe lines (runtime)
 //! Simplified implementation using direct file:line tracking
+//!
+//! Call `set_unsafe_coverage_merge(true)` to accumulate coverage across a
+//! suite of short-lived benchmark binaries: on the next registration or
+//! execution call, the tracker seeds itself from a prior `unsafe_coverage.stat`
+//! in the output directory, and `print_unsafe_coverage_stats` adds a
+//! `=== PER_FILE_SUMMARY ===` breakdown alongside the usual totals.
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::ffi::CStr;
 use std::os::raw::c_char;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Mutex;
 
+/// Whether to seed this process's registered/executed sets from a prior
+/// `unsafe_coverage.stat` on startup, so a suite of short-lived unsafe-
+/// benchmark binaries accumulates into one cumulative coverage figure
+/// instead of each reporting an isolated per-run number. Off by default;
+/// toggled via `set_unsafe_coverage_merge`.
+static MERGE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Guards `UnsafeCoverageTracker::seed_from_existing` so the prior-run file
+/// is parsed at most once per process, until `reset` clears this flag.
+static SEEDED: AtomicBool = AtomicBool::new(false);
+
 /// Simple coverage tracker - just track file:line strings
 struct UnsafeCoverageTracker {
     // Simple HashSets for registered and executed lines
@@ -47,12 +64,14 @@ impl UnsafeCoverageTracker {
     
     /// Register an unsafe line found at compile time
     fn register_line(&self, line: i64, file: *const c_char) {
+        self.seed_from_existing();
         let location = Self::make_location(line, file);
         self.registered_lines.lock().unwrap().insert(location);
     }
-    
+
     /// Track execution of an unsafe line at runtime
     fn track_execution(&self, line: i64, file: *const c_char) {
+        self.seed_from_existing();
         let location = Self::make_location(line, file);
         self.executed_lines.lock().unwrap().insert(location);
     }
@@ -88,8 +107,91 @@ impl UnsafeCoverageTracker {
         self.executed_lines.lock().unwrap().clear();
         self.stats_written.store(false, Ordering::Release);
         self.run_counter.store(0, Ordering::Release);
+        SEEDED.store(false, Ordering::Release);
     }
-    
+
+    /// If merge mode is enabled, seed `registered_lines`/`executed_lines`
+    /// from a prior run's `unsafe_coverage.stat` (if one exists) so a suite
+    /// of short-lived benchmark binaries accumulates into one cumulative
+    /// coverage figure instead of each reporting an isolated per-run number.
+    /// Runs at most once per process, and once more after each `reset`.
+    fn seed_from_existing(&self) {
+        if !MERGE_ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+        if SEEDED.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let path = crate::get_output_dir().join("unsafe_coverage.stat");
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(_) => return,
+        };
+
+        let mut registered = self.registered_lines.lock().unwrap();
+        let mut executed = self.executed_lines.lock().unwrap();
+
+        let mut section = "";
+        for line in contents.lines() {
+            match line {
+                "=== REGISTERED_LINES ===" | "=== EXECUTED_LINES ===" | "=== SUMMARY ===" => {
+                    section = line;
+                    continue;
+                }
+                _ if line.starts_with("=== RUN_") => {
+                    section = "";
+                    continue;
+                }
+                _ => {}
+            }
+            if line.is_empty() {
+                continue;
+            }
+            match section {
+                "=== REGISTERED_LINES ===" => {
+                    registered.insert(line.to_string());
+                }
+                "=== EXECUTED_LINES ===" => {
+                    executed.insert(line.to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Build a sorted `<file>: executed/registered (pct)` line per source
+    /// file, written as the `=== PER_FILE_SUMMARY ===` section after SUMMARY.
+    fn per_file_breakdown(registered: &HashSet<String>, executed: &HashSet<String>) -> String {
+        let mut per_file: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for location in registered.iter() {
+            if let Some((file, _)) = location.rsplit_once(':') {
+                per_file.entry(file).or_insert((0, 0)).1 += 1;
+            }
+        }
+        for location in executed.iter() {
+            if let Some((file, _)) = location.rsplit_once(':') {
+                if let Some(entry) = per_file.get_mut(file) {
+                    entry.0 += 1;
+                }
+            }
+        }
+
+        let mut output = String::new();
+        for (file, (executed_count, registered_count)) in per_file {
+            let pct = if registered_count > 0 {
+                (executed_count as f64 / registered_count as f64) * 100.0
+            } else {
+                0.0
+            };
+            output.push_str(&format!(
+                "{}: {}/{} ({:.2}%)\n",
+                file, executed_count, registered_count, pct
+            ));
+        }
+        output
+    }
+
     /// Write statistics to file and stderr
     fn write_stats(&self) {
         // Ensure single execution
@@ -114,10 +216,60 @@ impl UnsafeCoverageTracker {
         // Print to stderr (simple coverage percentage only)
         eprintln!("Coverage: {:.2}%", coverage);
 
+        if crate::structured_output_enabled() {
+            let _ = crate::write_record(
+                &Self::stats_as_json(run_num, &registered, &executed, registered_count, executed_count, coverage),
+                "unsafe_coverage.json",
+            );
+            return;
+        }
+
         // Append to file with new format
         self.write_detailed_stats(run_num, &registered, &executed, registered_count, executed_count, coverage);
     }
 
+    /// Build the structured (`UNSAFE_BENCH_OUTPUT_FORMAT=json`) rendering of
+    /// coverage stats. Each location is split into its `file` and `line`
+    /// under separate keys, rather than the combined `file:line` string the
+    /// plaintext report uses, so consumers can normalize volatile absolute
+    /// paths or line numbers without re-parsing a combined string.
+    fn stats_as_json(
+        run_num: usize,
+        registered: &HashSet<String>,
+        executed: &HashSet<String>,
+        registered_count: usize,
+        executed_count: usize,
+        coverage: f64,
+    ) -> crate::JsonValue {
+        use crate::JsonValue;
+
+        fn location_entries(locations: &HashSet<String>) -> Vec<JsonValue> {
+            let mut sorted: Vec<&String> = locations.iter().collect();
+            sorted.sort();
+            sorted.into_iter().map(|location| {
+                let (file, line) = location.rsplit_once(':').unwrap_or((location.as_str(), ""));
+                let line_value = match line.parse::<i64>() {
+                    Ok(n) => JsonValue::Int(n),
+                    Err(_) => JsonValue::Str(line.to_string()),
+                };
+                JsonValue::Object(vec![
+                    ("file", JsonValue::Str(file.to_string())),
+                    ("line", line_value),
+                ])
+            }).collect()
+        }
+
+        JsonValue::Object(vec![
+            ("kind", JsonValue::Str("unsafe_coverage".to_string())),
+            ("run", JsonValue::UInt(run_num as u64)),
+            ("registered_count", JsonValue::UInt(registered_count as u64)),
+            ("executed_count", JsonValue::UInt(executed_count as u64)),
+            ("coverage_percentage", JsonValue::Float(coverage)),
+            ("registered_lines", JsonValue::Array(location_entries(registered))),
+            ("executed_lines", JsonValue::Array(location_entries(executed))),
+        ])
+    }
+
     /// Write detailed statistics to file in new format
     fn write_detailed_stats(&self, run_num: usize, registered: &HashSet<String>, executed: &HashSet<String>,
                            registered_count: usize, executed_count: usize, coverage: f64) {
@@ -155,9 +307,54 @@ impl UnsafeCoverageTracker {
         output.push_str(&format!("run_timestamp={}\n", timestamp));
         output.push_str("\n");
 
+        if MERGE_ENABLED.load(Ordering::Relaxed) {
+            output.push_str("=== PER_FILE_SUMMARY ===\n");
+            output.push_str(&Self::per_file_breakdown(registered, executed));
+            output.push_str("\n");
+        }
+
         use crate::write_output;
         let _ = write_output(&output, "unsafe_coverage.stat");
     }
+
+    /// Write the unsafe-line coverage report in LCOV `.info` format: one
+    /// `SF:`/`DA:`/`LF:`/`LH:`/`end_of_record` block per source file, with
+    /// `DA:<line>,<hit>` set to `1` for lines present in `executed_lines`
+    /// and `0` otherwise. Unlike `write_detailed_stats`'s bespoke text blob,
+    /// this can be merged with normal coverage and browsed with `genhtml`.
+    fn write_lcov_stats(&self) {
+        let registered = self.registered_lines.lock().unwrap();
+        let executed = self.executed_lines.lock().unwrap();
+
+        let mut by_file: std::collections::BTreeMap<&str, Vec<i64>> = std::collections::BTreeMap::new();
+        for location in registered.iter() {
+            if let Some((file, line)) = location.rsplit_once(':') {
+                if let Ok(line) = line.parse::<i64>() {
+                    by_file.entry(file).or_default().push(line);
+                }
+            }
+        }
+
+        let mut output = String::new();
+        for (file, mut lines) in by_file {
+            lines.sort_unstable();
+            lines.dedup();
+
+            output.push_str(&format!("SF:{}\n", file));
+            let mut hit_lines = 0;
+            for line in &lines {
+                let hit = if executed.contains(&format!("{}:{}", file, line)) { 1 } else { 0 };
+                hit_lines += hit;
+                output.push_str(&format!("DA:{},{}\n", line, hit));
+            }
+            output.push_str(&format!("LF:{}\n", lines.len()));
+            output.push_str(&format!("LH:{}\n", hit_lines));
+            output.push_str("end_of_record\n");
+        }
+
+        use crate::write_output;
+        let _ = write_output(&output, "unsafe_coverage.info");
+    }
 }
 
 // Global tracker instance
@@ -208,8 +405,33 @@ pub extern "C" fn reset_unsafe_coverage_stats() {
     COVERAGE_TRACKER.reset();
 }
 
-/// Dump stats at program termination
-#[ctor::dtor]
+/// Enable or disable cumulative (cross-run) coverage merging: when enabled,
+/// the first `register_unsafe_line`/`track_unsafe_line_execution` call in
+/// this process seeds the tracker from a prior `unsafe_coverage.stat`, and
+/// `print_unsafe_coverage_stats` adds a `=== PER_FILE_SUMMARY ===` section
+/// after the SUMMARY of its plaintext report.
+#[no_mangle]
+pub extern "C" fn set_unsafe_coverage_merge(enabled: bool) {
+    MERGE_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Write the unsafe-line coverage report in LCOV `.info` format
+/// (`unsafe_coverage.info`), for tooling (e.g. `genhtml`) that expects
+/// standard coverage data rather than this module's bespoke text blob.
+#[no_mangle]
+pub extern "C" fn write_unsafe_coverage_lcov() {
+    COVERAGE_TRACKER.write_lcov_stats();
+}
+
+/// Dump stats at program termination, run via the crate's unified shutdown
+/// coordinator (`register_at_exit`) rather than our own `#[ctor::dtor]`, so
+/// coverage flushes in a single, ordered place alongside every other
+/// feature module.
 fn dump_coverage_at_exit() {
     COVERAGE_TRACKER.write_stats();
+}
+
+#[ctor::ctor]
+fn register_unsafe_coverage_shutdown() {
+    crate::register_at_exit(dump_coverage_at_exit);
 }
\ No newline at end of file
@@ -4,21 +4,66 @@
 //! to be used in MIR.
 
 use rustc_span::Span;
-use rustc_middle::thir::{Thir, BodyTy};
+use rustc_middle::thir::{Thir, BlockSafety, BodyTy, ExprId, ExprKind};
 use rustc_hir::Safety;
 use rustc_macros::{HashStable, TyDecodable, TyEncodable, TypeFoldable, TypeVisitable};
 use super::Body;
-use super::ty::TyCtxt;
+use super::ty::{self, TyCtxt};
 
 use phf::phf_set;
 
+/// The source-level reason a given site required an `unsafe` context, mirroring
+/// the categorization `rustc_mir_build`'s THIR unsafety checker uses internally.
+/// Kept separate from any single MIR/LLVM instruction category (load/store/call/
+/// cast) so downstream passes can weight their own counters by *why* a site is
+/// unsafe rather than only by *what kind* of instruction it lowers to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, TyEncodable, TyDecodable, HashStable, TypeFoldable, TypeVisitable)]
+pub enum UnsafeOpKind {
+    /// A call to a function or method declared `unsafe fn`.
+    CallToUnsafeFn,
+    /// An inline assembly block (`asm!`).
+    InlineAsm,
+    /// A dereference (`*p` or `p[i]`) of a raw pointer.
+    DerefRawPointer,
+    /// A read or write of a `static mut`.
+    AccessMutableStatic,
+    /// A read of a `static` declared in an `extern` block.
+    AccessExternStatic,
+    /// A field projection on a `union`.
+    UnionFieldAccess,
+    /// A write to a field whose enclosing type has layout constraints
+    /// (e.g. a `#[repr(packed)]` struct) that forbid ordinary mutation.
+    MutateLayoutConstrainedField,
+    /// A borrow of a field whose enclosing type has layout constraints
+    /// that forbid an ordinary reference (e.g. misaligned `#[repr(packed)]` fields).
+    BorrowLayoutConstrainedField,
+    /// A call to a function that has `#[target_feature]` attributes. Whether
+    /// the caller already enables those features is not checked.
+    CallWithTargetFeature,
+}
+
 #[derive(Clone, TyEncodable, TyDecodable, Debug, HashStable, TypeFoldable, TypeVisitable)]
 pub struct UnsafeCode {
     /// Whether this is an unsafe function.
-    /// TODO: handle this case.
     pub is_unsafe_fn: bool,
-    /// A list of Span of the function's unsafe blocks, if there are any.
-    pub unsafe_blocks: Option<Vec::<Span>>
+    /// Every unsafe site in the function body, paired with the reason it
+    /// required `unsafe`. Populated by walking `thir.exprs`; empty (not
+    /// `None`) when there are no unsafe sites, since callers generally want
+    /// to iterate it regardless of whether any entries exist.
+    pub unsafe_ops: Vec<(Span, UnsafeOpKind)>,
+    /// Spans of every explicit `unsafe { .. }` block in the body, regardless
+    /// of whether the enclosing function is itself `unsafe fn`. `unsafe_ops`
+    /// only covers the individual operation expressions, not the span of the
+    /// block around them (e.g. in `unsafe { let x = *p; helper(x); }`, the
+    /// `helper(x)` call isn't itself an unsafe op), so `in_unsafe` also
+    /// checks containment against these block spans to preserve the
+    /// block-level semantics callers expect.
+    pub unsafe_blocks: Vec<Span>,
+    /// Explicit `unsafe { .. }` blocks found inside a function that is
+    /// already `unsafe fn`. Such blocks grant no additional capability, so
+    /// these are tracked separately from `unsafe_ops` for a later lint to
+    /// flag as no-op wrappers worth stripping.
+    pub redundant_unsafe_blocks: Vec<Span>,
 }
 
 /// The set of Rust's native libraries. We ignore analyzing functions in these
@@ -40,15 +85,48 @@ static RUST_NATIVE_LIBS: phf::Set<&'static str> = phf_set! {
     "unwind"
 };
 
+/// Parse a `-C unsafe-ignore-crates=<comma-list>` / `-C
+/// unsafe-only-crates=<comma-list>` value into its crate names. Empty
+/// entries (from a trailing comma, or the flag being unset) are dropped.
+fn parse_crate_list(raw: &str) -> impl Iterator<Item = &str> {
+    raw.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
 /// Some native library functions, such as alloc::alloc::exchange_malloc(),
 /// are included in and compiled together with the source of the application.
 /// We ignore processing a function if it is in Rust's native libraries and
 /// if the unsafe_include_native_lib flag is not provided.
+///
+/// Two session flags give finer-grained control over this set, for users
+/// analyzing workspaces with vendored `std`-like crates, or who want to
+/// narrow analysis to a handful of crates:
+///   - `-C unsafe-only-crates=<comma-list>`: if given, analyze *only* the
+///     named crates and ignore every other crate, regardless of
+///     `RUST_NATIVE_LIBS` or `unsafe_include_native_lib`.
+///   - `-C unsafe-ignore-crates=<comma-list>`: additional crate names unioned
+///     with the built-in `RUST_NATIVE_LIBS` set.
+///
+/// When neither flag is given, this falls back to the original
+/// `RUST_NATIVE_LIBS`/`unsafe_include_native_lib` behavior, so existing runs
+/// are unchanged.
 pub fn ignore_fn<'tcx>(tcx: TyCtxt<'tcx>, body: &Body<'tcx>) -> bool {
+    let crate_name = tcx.crate_name(body.source.def_id().krate);
+    let crate_name = crate_name.as_str();
+
+    let only_crates = &tcx.sess.opts.cg.unsafe_only_crates;
+    if !only_crates.is_empty() {
+        return !parse_crate_list(only_crates).any(|name| name == crate_name);
+    }
+
+    if parse_crate_list(&tcx.sess.opts.cg.unsafe_ignore_crates).any(|name| name == crate_name) {
+        return true;
+    }
+
     if tcx.sess.opts.cg.unsafe_include_native_lib {
         return false;
     }
-    return RUST_NATIVE_LIBS.contains(tcx.crate_name(body.source.def_id().krate).as_str());
+
+    RUST_NATIVE_LIBS.contains(crate_name)
 }
 
 impl UnsafeCode {
@@ -56,16 +134,29 @@ impl UnsafeCode {
     pub fn new_empty() -> UnsafeCode {
         Self {
             is_unsafe_fn: false,
-            unsafe_blocks: None
+            unsafe_ops: Vec::new(),
+            unsafe_blocks: Vec::new(),
+            redundant_unsafe_blocks: Vec::new(),
         }
     }
 
     /// Collect unsafe code information of a function.
-    /// 
-    /// Currently we only collect two pieces of information:
-    /// 1. whether a function is unsafe
-    /// 2. the Span of unsafe blocks in a "safe" function.
-    pub fn new<'tcx>(thir: Option<&Thir<'tcx>>) -> UnsafeCode {
+    ///
+    /// Currently we collect:
+    /// 1. whether a function is unsafe,
+    /// 2. every unsafe operation in the function's body, classified by
+    ///    `UnsafeOpKind`, mirroring the categorization the THIR unsafety
+    ///    checker (`rustc_mir_build::check_unsafety`) uses,
+    /// 3. the span of every explicit `unsafe { .. }` block, so `in_unsafe`
+    ///    can preserve block-level containment, and
+    /// 4. which of those blocks are redundant because they sit inside a
+    ///    function that is already `unsafe fn`.
+    ///
+    /// We keep walking the body even once `is_unsafe_fn` is known, since an
+    /// `unsafe fn` can still contain no-op `unsafe {}` wrappers worth
+    /// flagging, and `unsafe_ops` is useful regardless of how the function
+    /// itself is declared.
+    pub fn new<'tcx>(tcx: TyCtxt<'tcx>, thir: Option<&Thir<'tcx>>) -> UnsafeCode {
         let mut unsafe_code = Self::new_empty();
 
         if let Some(thir) = thir {
@@ -73,20 +164,31 @@ impl UnsafeCode {
             if let BodyTy::Fn(fn_sig) = thir.body_type {
                 if let Safety::Unsafe = fn_sig.safety {
                     unsafe_code.is_unsafe_fn = true;
-                    return unsafe_code;
                 }
             }
 
-            // Collect unsafe blocks in a "safe" function.
+            // Collect unsafe operations, walking every expression in the
+            // THIR arena rather than only explicit `unsafe { .. }` blocks,
+            // so callers get source-level reasons even for operations that
+            // are unsafe regardless of the block they sit in.
+            for (_id, expr) in thir.exprs.iter_enumerated() {
+                if let Some(kind) = Self::classify_expr(tcx, thir, &expr.kind) {
+                    unsafe_code.unsafe_ops.push((expr.span, kind));
+                }
+            }
+
+            // Record every explicit `unsafe { .. }` block's span, regardless
+            // of whether the enclosing fn is itself unsafe, so `in_unsafe`
+            // can fall back to block-level containment. When the enclosing
+            // fn is already `unsafe fn`, the block also grants no additional
+            // capability, so it's additionally recorded as redundant for a
+            // later lint/diagnostic pass to suggest removing it.
             for block in &thir.blocks {
-                match block.safety_mode {
-                    rustc_middle::thir::BlockSafety::ExplicitUnsafe(_hir_id) => {
-                        if unsafe_code.unsafe_blocks.is_none() {
-                            unsafe_code.unsafe_blocks = Some(Vec::new());
-                        }
-                        unsafe_code.unsafe_blocks.as_mut().unwrap().push(block.span);
-                    },
-                    _ => {}
+                if let BlockSafety::ExplicitUnsafe(_hir_id) = block.safety_mode {
+                    unsafe_code.unsafe_blocks.push(block.span);
+                    if unsafe_code.is_unsafe_fn {
+                        unsafe_code.redundant_unsafe_blocks.push(block.span);
+                    }
                 }
             }
         }
@@ -94,20 +196,105 @@ impl UnsafeCode {
         unsafe_code
     }
 
+    /// Classify a single THIR expression as an unsafe operation, if it is one.
+    fn classify_expr<'tcx>(
+        tcx: TyCtxt<'tcx>,
+        thir: &Thir<'tcx>,
+        kind: &ExprKind<'tcx>,
+    ) -> Option<UnsafeOpKind> {
+        match kind {
+            ExprKind::Call { fun, .. } => {
+                let fun_ty = thir[*fun].ty;
+                if let ty::FnDef(def_id, _) = *fun_ty.kind() {
+                    if fun_ty.fn_sig(tcx).safety() == Safety::Unsafe {
+                        return Some(UnsafeOpKind::CallToUnsafeFn);
+                    }
+                    if !tcx.codegen_fn_attrs(def_id).target_features.is_empty() {
+                        return Some(UnsafeOpKind::CallWithTargetFeature);
+                    }
+                }
+                None
+            }
+            ExprKind::InlineAsm(_) => Some(UnsafeOpKind::InlineAsm),
+            ExprKind::Deref { arg } => {
+                thir[*arg].ty.is_unsafe_ptr().then_some(UnsafeOpKind::DerefRawPointer)
+            }
+            ExprKind::Index { lhs, .. } => {
+                thir[*lhs].ty.is_unsafe_ptr().then_some(UnsafeOpKind::DerefRawPointer)
+            }
+            ExprKind::StaticRef { def_id, .. } => {
+                if tcx.is_mutable_static(*def_id) {
+                    Some(UnsafeOpKind::AccessMutableStatic)
+                } else if tcx.is_foreign_item(*def_id) {
+                    Some(UnsafeOpKind::AccessExternStatic)
+                } else {
+                    None
+                }
+            }
+            ExprKind::Field { lhs, .. } => {
+                let lhs_ty = thir[*lhs].ty;
+                if let ty::Adt(adt_def, _) = *lhs_ty.kind() {
+                    if adt_def.is_union() {
+                        return Some(UnsafeOpKind::UnionFieldAccess);
+                    }
+                }
+                None
+            }
+            ExprKind::Assign { lhs, .. } | ExprKind::AssignOp { lhs, .. } => {
+                Self::layout_constrained_field(thir, *lhs)
+                    .then_some(UnsafeOpKind::MutateLayoutConstrainedField)
+            }
+            ExprKind::Borrow { arg, .. } | ExprKind::AddressOf { arg, .. } => {
+                Self::layout_constrained_field(thir, *arg)
+                    .then_some(UnsafeOpKind::BorrowLayoutConstrainedField)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `expr_id` is a field projection on a `#[repr(packed)]` type,
+    /// for which a direct reference or mutation could be misaligned. Other
+    /// layout constraints are not checked.
+    fn layout_constrained_field<'tcx>(thir: &Thir<'tcx>, expr_id: ExprId) -> bool {
+        if let ExprKind::Field { lhs, .. } = thir[expr_id].kind {
+            if let ty::Adt(adt_def, _) = *thir[lhs].ty.kind() {
+                return adt_def.repr().packed();
+            }
+        }
+        false
+    }
+
     /// Check whether an MIR statment/terminator (by its Span) is in an unsafe fn/block.
+    ///
+    /// Returns `true` for any span inside an `unsafe fn`, whether or not it
+    /// also sits inside a `redundant()` block there; use `redundant()`
+    /// separately to distinguish a "needed" unsafe context from one that is
+    /// already granted by the enclosing function.
     pub fn in_unsafe(&self, span: Span) -> bool {
         if self.is_unsafe_fn {
             return true;
         }
 
-        if let Some(blocks) = &self.unsafe_blocks {
-            for block in blocks {
-                if block.contains(span) {
-                    return true;
-                }
-            }
-        }
+        self.unsafe_blocks.iter().any(|block_span| block_span.contains(span))
+            || self.unsafe_ops.iter().any(|(op_span, _)| op_span.contains(span))
+    }
 
-        false
+    /// Spans of explicit `unsafe { .. }` blocks that grant no additional
+    /// capability because they sit inside a function that is already
+    /// `unsafe fn`. Empty for safe functions and for unsafe functions with
+    /// no such no-op wrappers.
+    pub fn redundant(&self) -> &[Span] {
+        &self.redundant_unsafe_blocks
+    }
+
+    /// Look up the `UnsafeOpKind` recorded for the unsafe site containing
+    /// `span`, if any, so MIR/LLVM passes can weight their own
+    /// unsafe-instruction counters (loads/stores/calls/casts) by the
+    /// source-level reason a site is unsafe rather than only its MIR shape.
+    pub fn op_kind_at(&self, span: Span) -> Option<UnsafeOpKind> {
+        self.unsafe_ops
+            .iter()
+            .find(|(op_span, _)| op_span.contains(span))
+            .map(|(_, kind)| *kind)
     }
-}
\ No newline at end of file
+}